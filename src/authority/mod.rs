@@ -0,0 +1,298 @@
+//! 本地权威区域(zone)子系统
+//!
+//! 借鉴 Alfis/hermes 的数据存储思路：每个 [`Zone`] 持有 SOA 参数和一组有序（`BTreeSet`，先按记录类型再按名字）的记录，
+//! 多个区域按 apex 域名存放在一个 [`RwLock`] 保护的 [`Authority`] 里，这样解析阶段可以
+//! 并发只读查找，未来重新加载区域时再获取写锁。
+
+use crate::config::ZoneConfig;
+use crate::protocol::{DnsRecord, QueryType};
+use std::collections::{BTreeSet, HashMap};
+use tokio::fs;
+use tokio::sync::RwLock;
+use tracing::{error, info};
+
+/// 一个权威区域及其 SOA 参数
+#[derive(Debug, Clone)]
+pub struct Zone {
+    pub domain: String,
+    pub m_name: String,
+    pub r_name: String,
+    pub serial: u32,
+    pub refresh: u32,
+    pub retry: u32,
+    pub expire: u32,
+    pub minimum: u32,
+    pub records: BTreeSet<DnsRecord>,
+}
+
+impl Zone {
+    fn soa_record(&self) -> DnsRecord {
+        DnsRecord::Soa {
+            domain: self.domain.clone(),
+            m_name: self.m_name.clone(),
+            r_name: self.r_name.clone(),
+            serial: self.serial,
+            refresh: self.refresh,
+            retry: self.retry,
+            expire: self.expire,
+            minimum: self.minimum,
+            ttl: self.minimum,
+        }
+    }
+
+    fn lookup(&self, name: &str, qtype: QueryType) -> ZoneLookup {
+        let apex = self.domain.to_lowercase();
+        let name_exists = name == apex || self.records.iter().any(|r| r.domain().eq_ignore_ascii_case(name));
+
+        if !name_exists {
+            return ZoneLookup::NxDomain;
+        }
+
+        let matching: Vec<DnsRecord> = self
+            .records
+            .iter()
+            .filter(|r| r.domain().eq_ignore_ascii_case(name) && r.query_type() == qtype)
+            .cloned()
+            .collect();
+
+        if matching.is_empty() {
+            ZoneLookup::NoData
+        } else {
+            ZoneLookup::Records(matching)
+        }
+    }
+}
+
+enum ZoneLookup {
+    Records(Vec<DnsRecord>),
+    NoData,
+    NxDomain,
+}
+
+/// 在本地区域中查到的结果，调用方据此决定如何构造响应报文
+pub enum AuthorityAnswer {
+    /// 名字和类型都匹配，返回具体记录
+    Records(Vec<DnsRecord>),
+    /// 名字存在但该类型没有记录，NODATA，需要在 authority 段带上区域 SOA
+    NoData(DnsRecord),
+    /// 名字不在区域内，NXDOMAIN，同样需要带上区域 SOA
+    NxDomain(DnsRecord),
+}
+
+/// 权威区域存储，按 zone apex（小写）索引
+pub struct Authority {
+    zones: RwLock<HashMap<String, Zone>>,
+}
+
+impl Authority {
+    pub fn empty() -> Self {
+        Self {
+            zones: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// 按配置加载所有区域文件（显式列表 + 目录扫描）；单个文件加载失败只记录错误，不影响其它区域
+    pub async fn load(config: &ZoneConfig) -> Self {
+        let authority = Self::empty();
+        if !config.enabled {
+            return authority;
+        }
+
+        for path in &config.zone_files {
+            authority.load_zone_file_into(path).await;
+        }
+
+        for dir in &config.zone_dirs {
+            match collect_zone_files(dir).await {
+                Ok(paths) => {
+                    for path in paths {
+                        authority.load_zone_file_into(&path).await;
+                    }
+                }
+                Err(e) => error!("扫描区域目录 {} 失败: {}", dir, e),
+            }
+        }
+
+        authority
+    }
+
+    /// 加载单个区域文件并插入存储，失败只记录错误
+    async fn load_zone_file_into(&self, path: &str) {
+        match load_zone_file(path).await {
+            Ok(zone) => {
+                info!("加载区域 {} ({} 条记录)", zone.domain, zone.records.len());
+                let mut zones = self.zones.write().await;
+                zones.insert(zone.domain.to_lowercase(), zone);
+            }
+            Err(e) => error!("加载区域文件 {} 失败: {}", path, e),
+        }
+    }
+
+    /// 查找 `qname` 落在哪个本地区域内（取最长匹配的 apex），并在其中查找 `qtype`
+    pub async fn resolve(&self, qname: &str, qtype: QueryType) -> Option<AuthorityAnswer> {
+        let qname_lower = qname.to_lowercase();
+        let zones = self.zones.read().await;
+
+        let zone = zones
+            .values()
+            .filter(|zone| {
+                let apex = zone.domain.to_lowercase();
+                qname_lower == apex || qname_lower.ends_with(&format!(".{apex}"))
+            })
+            .max_by_key(|zone| zone.domain.len())?;
+
+        match zone.lookup(&qname_lower, qtype) {
+            ZoneLookup::Records(records) => Some(AuthorityAnswer::Records(records)),
+            ZoneLookup::NoData => Some(AuthorityAnswer::NoData(zone.soa_record())),
+            ZoneLookup::NxDomain => Some(AuthorityAnswer::NxDomain(zone.soa_record())),
+        }
+    }
+}
+
+impl Default for Authority {
+    fn default() -> Self {
+        Self::empty()
+    }
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct ZoneFile {
+    domain: String,
+    soa: ZoneSoaDef,
+    #[serde(default)]
+    records: Vec<ZoneRecordDef>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct ZoneSoaDef {
+    m_name: String,
+    r_name: String,
+    serial: u32,
+    refresh: u32,
+    retry: u32,
+    expire: u32,
+    minimum: u32,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct ZoneRecordDef {
+    name: String,
+    #[serde(rename = "type")]
+    rtype: String,
+    value: String,
+    #[serde(default)]
+    ttl: Option<u32>,
+    #[serde(default)]
+    priority: Option<u16>,
+}
+
+/// 扫描目录下所有 `.json` 文件作为区域文件，按文件名排序以保证加载顺序确定
+async fn collect_zone_files(dir: &str) -> Result<Vec<String>, AuthorityError> {
+    let mut entries = fs::read_dir(dir)
+        .await
+        .map_err(|e| AuthorityError::Io(e.to_string()))?;
+
+    let mut paths = Vec::new();
+    while let Some(entry) = entries
+        .next_entry()
+        .await
+        .map_err(|e| AuthorityError::Io(e.to_string()))?
+    {
+        let path = entry.path();
+        if path.extension().is_some_and(|ext| ext == "json") {
+            paths.push(path.to_string_lossy().into_owned());
+        }
+    }
+    paths.sort();
+
+    Ok(paths)
+}
+
+/// 以 JSON 格式加载一个区域文件，`name` 为 `@` 表示区域 apex 本身
+async fn load_zone_file(path: &str) -> Result<Zone, AuthorityError> {
+    let content = fs::read_to_string(path)
+        .await
+        .map_err(|e| AuthorityError::Io(e.to_string()))?;
+    let zone_file: ZoneFile =
+        serde_json::from_str(&content).map_err(|e| AuthorityError::Parse(e.to_string()))?;
+
+    let mut records = BTreeSet::new();
+    for def in &zone_file.records {
+        let domain = if def.name == "@" {
+            zone_file.domain.clone()
+        } else {
+            format!("{}.{}", def.name, zone_file.domain)
+        };
+        let ttl = def.ttl.unwrap_or(zone_file.soa.minimum);
+
+        let record = match def.rtype.to_ascii_uppercase().as_str() {
+            "A" => DnsRecord::A {
+                domain,
+                addr: def
+                    .value
+                    .parse()
+                    .map_err(|_| AuthorityError::Parse(format!("非法的 A 记录地址: {}", def.value)))?,
+                ttl,
+            },
+            "AAAA" => DnsRecord::Aaaa {
+                domain,
+                addr: def.value.parse().map_err(|_| {
+                    AuthorityError::Parse(format!("非法的 AAAA 记录地址: {}", def.value))
+                })?,
+                ttl,
+            },
+            "CNAME" => DnsRecord::Cname {
+                domain,
+                host: def.value.clone(),
+                ttl,
+            },
+            "NS" => DnsRecord::Ns {
+                domain,
+                host: def.value.clone(),
+                ttl,
+            },
+            "MX" => DnsRecord::Mx {
+                domain,
+                priority: def.priority.unwrap_or(10),
+                host: def.value.clone(),
+                ttl,
+            },
+            "TXT" => DnsRecord::Txt {
+                domain,
+                data: def.value.clone().into_bytes(),
+                ttl,
+            },
+            other => return Err(AuthorityError::Parse(format!("不支持的记录类型: {}", other))),
+        };
+        records.insert(record);
+    }
+
+    Ok(Zone {
+        domain: zone_file.domain,
+        m_name: zone_file.soa.m_name,
+        r_name: zone_file.soa.r_name,
+        serial: zone_file.soa.serial,
+        refresh: zone_file.soa.refresh,
+        retry: zone_file.soa.retry,
+        expire: zone_file.soa.expire,
+        minimum: zone_file.soa.minimum,
+        records,
+    })
+}
+
+#[derive(Debug)]
+pub enum AuthorityError {
+    Io(String),
+    Parse(String),
+}
+
+impl std::fmt::Display for AuthorityError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AuthorityError::Io(msg) => write!(f, "读取区域文件失败: {}", msg),
+            AuthorityError::Parse(msg) => write!(f, "解析区域文件失败: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for AuthorityError {}
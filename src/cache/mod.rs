@@ -1,4 +1,6 @@
 use crate::config::CacheConfig;
+use crate::protocol::{BytePacketBuffer, DnsHeader, DnsPacket, DnsRecord, PacketBuffer, ProtocolResult, ResultCode};
+use crate::utils::extract_query_id;
 use std::collections::HashMap;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
@@ -7,26 +9,40 @@ use tracing::{debug, info};
 
 pub type DnsMessage = Vec<u8>;
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CacheKind {
+    Positive,
+    /// RFC 2308 否定缓存：NXDOMAIN 或 NOERROR/NODATA
+    Negative,
+}
+
 /// DNS缓存条目
 #[derive(Debug, Clone)]
 struct CacheEntry {
     response: DnsMessage,
     created_at: Instant,
     ttl: Duration,
+    kind: CacheKind,
 }
 
 impl CacheEntry {
-    fn new(response: DnsMessage, ttl: Duration) -> Self {
+    fn new(response: DnsMessage, ttl: Duration, kind: CacheKind) -> Self {
         Self {
             response,
             created_at: Instant::now(),
             ttl,
+            kind,
         }
     }
 
     fn is_expired(&self) -> bool {
         self.created_at.elapsed() > self.ttl
     }
+
+    /// 距离过期还剩多久，用于命中时衰减返回给客户端的 TTL
+    fn remaining_ttl(&self) -> Duration {
+        self.ttl.saturating_sub(self.created_at.elapsed())
+    }
 }
 
 /// DNS缓存管理器
@@ -49,32 +65,111 @@ impl DnsCache {
         }
     }
 
-    /// 生成缓存键
-    fn generate_cache_key(&self, query: &DnsMessage) -> String {
-        // 简单的哈希实现，实际应该解析DNS查询来生成更准确的键
-        format!("{:x}", md5::compute(query))
+    /// 由归一化的问题集合生成缓存键：只取小写 qname + qtype + qclass，
+    /// 忽略事务 ID 等每次查询都会变化的字段，这样同一个问题才能命中同一个条目
+    fn generate_cache_key(&self, query: &DnsMessage) -> Option<String> {
+        let packet = DnsPacket::from_bytes(query).ok()?;
+        if packet.questions.is_empty() {
+            return None;
+        }
+
+        let mut parts: Vec<String> = packet
+            .questions
+            .iter()
+            .map(|q| format!("{}:{}:{}", q.name.to_lowercase(), q.qtype.to_num(), q.qclass))
+            .collect();
+        parts.sort();
+        Some(parts.join("|"))
     }
 
-    /// 从缓存获取响应
+    /// 从缓存获取响应，命中时把存储响应的事务 ID 换成本次查询的 ID，
+    /// 并把记录 TTL 按距离写入已经过去的时间衰减，让下游看到递减的 TTL
     pub async fn get(&self, query: &DnsMessage) -> Option<DnsMessage> {
         if !self.enabled {
             return None;
         }
 
-        let key = self.generate_cache_key(query);
+        let key = self.generate_cache_key(query)?;
+        let query_id = extract_query_id(query)?;
+
         let cache = self.cache.read().await;
+        let entry = cache.get(&key)?;
 
-        if let Some(entry) = cache.get(&key) {
-            if !entry.is_expired() {
-                debug!("缓存命中: {}", key);
-                return Some(entry.response.clone());
-            } else {
-                debug!("缓存过期: {}", key);
-            }
+        if entry.is_expired() {
+            debug!("缓存过期: {}", key);
+            return None;
+        }
+
+        debug!("缓存命中: {} ({:?})", key, entry.kind);
+        Some(Self::rewrite_response(
+            &entry.response,
+            query_id,
+            entry.remaining_ttl(),
+        ))
+    }
+
+    /// 直接在原始字节上改写事务 ID 和每条记录的 TTL，而不是解析成 `DnsPacket`
+    /// 再整体重新序列化——命中缓存是最热的路径，不应该依赖 writer 对每种记录
+    /// 类型（尤其是尚未建模的类型）都能无损往返
+    fn rewrite_response(stored: &DnsMessage, query_id: u16, remaining_ttl: Duration) -> DnsMessage {
+        if stored.len() < 12 {
+            // 不足一个 header 长度，不是合法报文
+            return stored.clone();
+        }
+
+        let mut bytes = stored.clone();
+        bytes[0] = (query_id >> 8) as u8;
+        bytes[1] = (query_id & 0xFF) as u8;
+
+        let remaining_secs = remaining_ttl.as_secs() as u32;
+
+        let Ok(ttl_offsets) = Self::locate_record_ttls(stored) else {
+            // 定位失败就只改 ID，至少事务匹配不会出错
+            return bytes;
+        };
+
+        for offset in ttl_offsets {
+            let current = u32::from_be_bytes([
+                bytes[offset],
+                bytes[offset + 1],
+                bytes[offset + 2],
+                bytes[offset + 3],
+            ]);
+            let capped = current.min(remaining_secs);
+            bytes[offset..offset + 4].copy_from_slice(&capped.to_be_bytes());
         }
 
-        debug!("缓存未命中: {}", key);
-        None
+        bytes
+    }
+
+    /// 走一遍线格式定位每条资源记录 TTL 字段的起始偏移，不需要把记录解析成结构化数据
+    fn locate_record_ttls(stored: &DnsMessage) -> ProtocolResult<Vec<usize>> {
+        let mut buffer = BytePacketBuffer::new(stored);
+        let mut header = DnsHeader::new();
+        header.read(&mut buffer)?;
+
+        for _ in 0..header.questions {
+            buffer.read_qname()?;
+            buffer.read_u16()?; // qtype
+            buffer.read_u16()?; // qclass
+        }
+
+        let total_records = header.answers as usize
+            + header.authoritative_entries as usize
+            + header.resource_entries as usize;
+        let mut offsets = Vec::with_capacity(total_records);
+
+        for _ in 0..total_records {
+            buffer.read_qname()?;
+            buffer.read_u16()?; // type
+            buffer.read_u16()?; // class
+            offsets.push(buffer.pos());
+            buffer.read_u32()?; // ttl
+            let rdlen = buffer.read_u16()?;
+            buffer.step(rdlen as usize)?;
+        }
+
+        Ok(offsets)
     }
 
     /// 将响应存入缓存
@@ -83,7 +178,46 @@ impl DnsCache {
             return;
         }
 
-        let key = self.generate_cache_key(query);
+        let Some(key) = self.generate_cache_key(query) else {
+            debug!("查询无法解析，跳过缓存");
+            return;
+        };
+
+        let Ok(packet) = DnsPacket::from_bytes(&response) else {
+            debug!("响应无法解析，跳过缓存: {}", key);
+            return;
+        };
+
+        let rcode = packet.header.result_code();
+        let is_negative = rcode == ResultCode::NxDomain
+            || (rcode == ResultCode::NoError && packet.answers.is_empty());
+
+        let ttl = if is_negative {
+            // RFC 2308: 否定缓存的 TTL 取自权威区 SOA 记录的 minimum 字段
+            let soa_minimum = packet.authorities.iter().find_map(|r| match r {
+                DnsRecord::Soa { minimum, .. } => Some(*minimum),
+                _ => None,
+            });
+            match soa_minimum {
+                Some(minimum) => {
+                    Duration::from_secs(minimum as u64).clamp(self.min_ttl, self.max_ttl)
+                }
+                None => self.min_ttl,
+            }
+        } else {
+            let min_rr_ttl = packet.answers.iter().map(|r| r.ttl()).min();
+            match min_rr_ttl.or(ttl_hint) {
+                Some(secs) => Duration::from_secs(secs as u64).clamp(self.min_ttl, self.max_ttl),
+                None => self.min_ttl,
+            }
+        };
+
+        let kind = if is_negative {
+            CacheKind::Negative
+        } else {
+            CacheKind::Positive
+        };
+
         let mut cache = self.cache.write().await;
 
         // 如果缓存已满，清理过期条目或删除最旧的条目
@@ -98,19 +232,10 @@ impl DnsCache {
             }
         }
 
-        // 计算TTL
-        let ttl = if let Some(hint) = ttl_hint {
-            let ttl_duration = Duration::from_secs(hint as u64);
-            // 确保TTL在允许范围内
-            ttl_duration.max(self.min_ttl).min(self.max_ttl)
-        } else {
-            self.min_ttl
-        };
-
-        let entry = CacheEntry::new(response, ttl);
+        let entry = CacheEntry::new(response, ttl, kind);
         cache.insert(key.clone(), entry);
 
-        debug!("缓存存储: {}, TTL: {:?}", key, ttl);
+        debug!("缓存存储: {}, TTL: {:?}, 类型: {:?}", key, ttl, kind);
     }
 
     /// 清理过期的缓存条目
@@ -161,12 +286,22 @@ impl DnsCache {
         let cache = self.cache.read().await;
         let total_entries = cache.len();
         let expired_entries = cache.values().filter(|entry| entry.is_expired()).count();
+        let positive_entries = cache
+            .values()
+            .filter(|entry| entry.kind == CacheKind::Positive)
+            .count();
+        let negative_entries = cache
+            .values()
+            .filter(|entry| entry.kind == CacheKind::Negative)
+            .count();
 
         CacheStats {
             total_entries,
             expired_entries,
             active_entries: total_entries - expired_entries,
             max_size: self.max_size,
+            positive_entries,
+            negative_entries,
         }
     }
 }
@@ -177,4 +312,6 @@ pub struct CacheStats {
     pub expired_entries: usize,
     pub active_entries: usize,
     pub max_size: usize,
+    pub positive_entries: usize,
+    pub negative_entries: usize,
 }
@@ -8,7 +8,7 @@ pub struct Config {
     pub server: ServerConfig,
     pub upstreams: Vec<UpstreamConfig>,
     pub cache: CacheConfig,
-    pub filters: FilterConfig,
+    pub zones: ZoneConfig,
     pub middleware: MiddlewareConfig,
 }
 
@@ -18,6 +18,40 @@ pub struct ServerConfig {
     pub tcp_enabled: bool,
     pub udp_enabled: bool,
     pub timeout: u64, // 超时时间(秒)
+    /// 是否从系统 `/etc/resolv.conf` 发现上游服务器，和 `upstreams` 中手动配置的服务器合并
+    #[serde(default)]
+    pub upstreams_from_resolv_conf: bool,
+    /// `upstreams_from_resolv_conf` 启用时读取的文件路径
+    #[serde(default = "default_resolv_conf_path")]
+    pub resolv_conf_path: String,
+    /// TCP 允许的最大并发连接数
+    #[serde(default = "default_max_tcp_connections")]
+    pub max_tcp_connections: usize,
+    /// TCP 连接在两次查询之间允许空闲的秒数，超时则关闭连接
+    #[serde(default = "default_tcp_idle_timeout")]
+    pub tcp_idle_timeout: u64,
+    /// UDP 监听 socket 数量：通过 `SO_REUSEPORT` 绑定多个 socket 各跑一条独立的
+    /// 接收循环，由内核在它们之间负载均衡，默认等于 CPU 核心数
+    #[serde(default = "default_udp_socket_count")]
+    pub udp_socket_count: usize,
+}
+
+fn default_udp_socket_count() -> usize {
+    std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1)
+}
+
+fn default_max_tcp_connections() -> usize {
+    256
+}
+
+fn default_tcp_idle_timeout() -> u64 {
+    30
+}
+
+fn default_resolv_conf_path() -> String {
+    "/etc/resolv.conf".to_string()
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -27,6 +61,12 @@ pub struct UpstreamConfig {
     pub protocol: Protocol,
     pub priority: u32,
     pub timeout: u64,
+    /// `Protocol::DoT` 校验证书时使用的服务器名称（SNI），默认退化为 `addr` 的 IP
+    #[serde(default)]
+    pub tls_server_name: Option<String>,
+    /// `Protocol::DoH` 的查询 URL，例如 `https://cloudflare-dns.com/dns-query`
+    #[serde(default)]
+    pub doh_url: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -45,12 +85,16 @@ pub struct CacheConfig {
     pub ttl_max: u64,
 }
 
+/// 本地权威区域配置
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct FilterConfig {
-    pub blocklist_enabled: bool,
-    pub blocklist_files: Vec<String>,
-    pub allowlist_enabled: bool,
-    pub allowlist_domains: Vec<String>,
+pub struct ZoneConfig {
+    pub enabled: bool,
+    /// 区域文件路径列表（JSON 格式，见 `authority` 模块）
+    pub zone_files: Vec<String>,
+    /// 额外扫描的区域文件目录：启动时会加载其中每一个 `.json` 文件，
+    /// 便于把区域文件整个目录挂载进容器而不必在配置里逐个列出
+    #[serde(default)]
+    pub zone_dirs: Vec<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -58,6 +102,45 @@ pub struct MiddlewareConfig {
     pub logging_enabled: bool,
     pub metrics_enabled: bool,
     pub rate_limiting: RateLimitConfig,
+    pub blocklist: BlocklistConfig,
+    /// Prometheus 文本格式指标的抓取端点监听地址，`None` 表示不启动该端点
+    #[serde(default)]
+    pub metrics_listen_addr: Option<SocketAddr>,
+}
+
+/// `BlocklistMiddleware` 的配置：从规则文件加载的域名黑名单
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlocklistConfig {
+    pub enabled: bool,
+    /// 规则文件路径列表，支持按后缀匹配的域名（含 `*.domain` 写法）、`regex:` 前缀
+    /// 的正则规则，以及 hosts 文件格式（`0.0.0.0 domain`）
+    pub rule_files: Vec<String>,
+    /// 白名单文件路径列表，格式同 `rule_files`（不支持 `regex:`），命中时优先于
+    /// 黑名单短路放行
+    #[serde(default)]
+    pub allowlist_files: Vec<String>,
+    /// 命中规则后如何处理
+    #[serde(default)]
+    pub action: BlocklistAction,
+    /// 定期从磁盘热重载规则文件的周期（秒），0 表示关闭定期热重载
+    #[serde(default = "default_blocklist_reload_interval")]
+    pub reload_interval_secs: u64,
+}
+
+fn default_blocklist_reload_interval() -> u64 {
+    300
+}
+
+/// 命中 blocklist 规则后的处理方式
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub enum BlocklistAction {
+    /// 返回 NXDOMAIN
+    #[default]
+    NxDomain,
+    /// 返回指向 0.0.0.0 / :: 的 A/AAAA 记录（sinkhole）
+    Sinkhole,
+    /// 直接丢弃请求，不回任何响应
+    Drop,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -65,6 +148,36 @@ pub struct RateLimitConfig {
     pub enabled: bool,
     pub requests_per_second: u32,
     pub burst_size: u32,
+    /// LRU 中最多同时跟踪的客户端（按聚合后的前缀）数量，防止伪造源地址的洪泛把
+    /// 限流桶表撑爆导致内存耗尽
+    #[serde(default = "default_max_tracked_clients")]
+    pub max_tracked_clients: usize,
+    /// 超过这么久没有再发请求的客户端会被后台任务清理出限流表
+    #[serde(default = "default_rate_limit_idle_timeout")]
+    pub idle_timeout_secs: u64,
+    /// 限流按 IPv4 地址的前多少位聚合（例如 24 表示按 /24 子网限流），
+    /// 避免攻击者在同一子网内更换源地址绕过限制
+    #[serde(default = "default_ipv4_prefix_len")]
+    pub ipv4_prefix_len: u8,
+    /// 限流按 IPv6 地址的前多少位聚合
+    #[serde(default = "default_ipv6_prefix_len")]
+    pub ipv6_prefix_len: u8,
+}
+
+fn default_max_tracked_clients() -> usize {
+    10_000
+}
+
+fn default_rate_limit_idle_timeout() -> u64 {
+    300
+}
+
+fn default_ipv4_prefix_len() -> u8 {
+    32
+}
+
+fn default_ipv6_prefix_len() -> u8 {
+    64
 }
 
 impl Config {
@@ -85,6 +198,11 @@ impl Config {
                 tcp_enabled: true,
                 udp_enabled: true,
                 timeout: 5,
+                upstreams_from_resolv_conf: false,
+                resolv_conf_path: default_resolv_conf_path(),
+                max_tcp_connections: default_max_tcp_connections(),
+                tcp_idle_timeout: default_tcp_idle_timeout(),
+                udp_socket_count: default_udp_socket_count(),
             },
             upstreams: vec![
                 UpstreamConfig {
@@ -93,6 +211,8 @@ impl Config {
                     protocol: Protocol::UDP,
                     priority: 1,
                     timeout: 5,
+                    tls_server_name: None,
+                    doh_url: None,
                 },
                 UpstreamConfig {
                     name: "Google".to_string(),
@@ -100,6 +220,8 @@ impl Config {
                     protocol: Protocol::UDP,
                     priority: 2,
                     timeout: 5,
+                    tls_server_name: None,
+                    doh_url: None,
                 },
             ],
             cache: CacheConfig {
@@ -108,11 +230,10 @@ impl Config {
                 ttl_min: 60,
                 ttl_max: 3600,
             },
-            filters: FilterConfig {
-                blocklist_enabled: false,
-                blocklist_files: vec![],
-                allowlist_enabled: false,
-                allowlist_domains: vec![],
+            zones: ZoneConfig {
+                enabled: false,
+                zone_files: vec![],
+                zone_dirs: vec![],
             },
             middleware: MiddlewareConfig {
                 logging_enabled: true,
@@ -121,7 +242,19 @@ impl Config {
                     enabled: true,
                     requests_per_second: 100,
                     burst_size: 200,
+                    max_tracked_clients: default_max_tracked_clients(),
+                    idle_timeout_secs: default_rate_limit_idle_timeout(),
+                    ipv4_prefix_len: default_ipv4_prefix_len(),
+                    ipv6_prefix_len: default_ipv6_prefix_len(),
+                },
+                blocklist: BlocklistConfig {
+                    enabled: false,
+                    rule_files: vec![],
+                    allowlist_files: vec![],
+                    action: BlocklistAction::NxDomain,
+                    reload_interval_secs: default_blocklist_reload_interval(),
                 },
+                metrics_listen_addr: None,
             },
         }
     }
@@ -1,7 +1,10 @@
+mod authority;
 mod cache;
 mod config;
-mod filter;
+mod message;
 mod middleware;
+mod protocol;
+mod resolv;
 mod resolver;
 mod server;
 mod utils;
@@ -0,0 +1,25 @@
+//! 面向中间件管道的结构化 DNS 消息类型
+//!
+//! 中间件以前只能看到原始报文字节 (`Vec<u8>`)，无法据此做路由/过滤决策。
+//! 这里在已有的 `protocol` 模块之上做一层薄封装：`Message` 直接复用
+//! `protocol::DnsPacket` 的解析/序列化逻辑，避免重复实现 DNS 线格式。
+
+use crate::protocol::{DnsPacket, ProtocolError};
+
+/// 解析后的 DNS 报文，中间件可以直接读取/修改其中的问题和资源记录
+pub type Message = DnsPacket;
+
+/// DNS 域名（已解压缩为点分字符串）
+pub type Name = String;
+
+pub use crate::protocol::{DnsHeader, DnsQuestion as Question, DnsRecord};
+
+/// 从线格式字节解析出结构化消息
+pub fn parse(data: &[u8]) -> Result<Message, ProtocolError> {
+    DnsPacket::from_bytes(data)
+}
+
+/// 将消息重新序列化为线格式字节
+pub fn to_bytes(message: &mut Message) -> Result<Vec<u8>, ProtocolError> {
+    message.to_bytes()
+}
@@ -0,0 +1,311 @@
+use super::{Middleware, MiddlewareError, MiddlewareResult};
+use crate::config::BlocklistAction;
+use crate::message::Message;
+use crate::protocol::{DnsPacket, DnsRecord, QueryType, ResultCode};
+use async_trait::async_trait;
+use regex::Regex;
+use std::collections::HashSet;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use tokio::fs;
+use tokio::sync::RwLock;
+use tracing::{debug, error, info};
+
+/// 一次加载的规则集合：域名（含 hosts 文件/`*.domain` 写法，按后缀语义匹配）、
+/// 正则（`regex:`），以及优先于黑名单短路的白名单域名
+#[derive(Debug, Default)]
+struct BlocklistRules {
+    domains: HashSet<String>,
+    regexes: Vec<Regex>,
+    allowed: HashSet<String>,
+}
+
+impl BlocklistRules {
+    /// 白名单优先：命中白名单直接放行，即使同时命中了黑名单
+    fn matches(&self, name: &str) -> bool {
+        if matches_parent_labels(&self.allowed, name) {
+            return false;
+        }
+
+        matches_parent_labels(&self.domains, name) || self.regexes.iter().any(|re| re.is_match(name))
+    }
+}
+
+/// 依次检查 `name` 本身及其每一级父域（去掉最左边的标签），命中集合中的任意
+/// 一级即视为匹配 —— 这样 `example.com` 这条规则也会覆盖 `ads.example.com`
+fn matches_parent_labels(set: &HashSet<String>, name: &str) -> bool {
+    let mut labels: Vec<&str> = name.split('.').collect();
+
+    while !labels.is_empty() {
+        if set.contains(&labels.join(".")) {
+            return true;
+        }
+        labels.remove(0);
+    }
+
+    false
+}
+
+/// 从规则文件加载规则：一行一条，`#` 开头或行内 `#` 之后视为注释，
+/// `*.domain` 前缀和不带前缀的裸域名按相同的后缀语义处理（`*.` 只是声明意图,
+/// 并不改变匹配范围），`regex:` 前缀表示正则；也兼容公共黑名单常见的
+/// hosts 文件格式（`0.0.0.0 domain` / `127.0.0.1 domain`），行首是 IP 地址时
+/// 取第二个字段作为域名
+async fn load_domain_rules(files: &[String]) -> (HashSet<String>, Vec<Regex>) {
+    let mut domains = HashSet::new();
+    let mut regexes = Vec::new();
+
+    for path in files {
+        let content = match fs::read_to_string(path).await {
+            Ok(content) => content,
+            Err(e) => {
+                error!("加载 blocklist 规则文件 {} 失败: {}", path, e);
+                continue;
+            }
+        };
+
+        let mut count = 0;
+        for line in content.lines() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            if let Some(pattern) = line.strip_prefix("regex:") {
+                match Regex::new(pattern) {
+                    Ok(re) => {
+                        regexes.push(re);
+                        count += 1;
+                    }
+                    Err(e) => error!("规则文件 {} 中的正则 {} 无效: {}", path, pattern, e),
+                }
+                continue;
+            }
+
+            let Some(domain) = parse_domain_field(line) else {
+                continue;
+            };
+            let domain = domain.strip_prefix("*.").unwrap_or(domain);
+
+            domains.insert(domain.to_lowercase());
+            count += 1;
+        }
+
+        info!("从文件 {} 加载了 {} 条 blocklist 规则", path, count);
+    }
+
+    (domains, regexes)
+}
+
+/// 加载纯域名列表（白名单用），格式同 `load_domain_rules` 但不支持 `regex:`
+async fn load_domain_set(files: &[String]) -> HashSet<String> {
+    let mut domains = HashSet::new();
+
+    for path in files {
+        let content = match fs::read_to_string(path).await {
+            Ok(content) => content,
+            Err(e) => {
+                error!("加载 blocklist 白名单文件 {} 失败: {}", path, e);
+                continue;
+            }
+        };
+
+        let mut count = 0;
+        for line in content.lines() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let Some(domain) = parse_domain_field(line) else {
+                continue;
+            };
+            let domain = domain.strip_prefix("*.").unwrap_or(domain);
+
+            domains.insert(domain.to_lowercase());
+            count += 1;
+        }
+
+        info!("从文件 {} 加载了 {} 条白名单域名", path, count);
+    }
+
+    domains
+}
+
+async fn load_rules(rule_files: &[String], allowlist_files: &[String]) -> BlocklistRules {
+    let (domains, regexes) = load_domain_rules(rule_files).await;
+    let allowed = load_domain_set(allowlist_files).await;
+
+    BlocklistRules {
+        domains,
+        regexes,
+        allowed,
+    }
+}
+
+/// 从一行规则中取出域名字段：如果第一个字段是 IP 地址（hosts 文件格式），
+/// 域名取第二个字段；否则整行（去掉首尾空白）就是域名
+fn parse_domain_field(line: &str) -> Option<&str> {
+    let mut fields = line.split_whitespace();
+    let first = fields.next()?;
+
+    if first.parse::<IpAddr>().is_ok() {
+        fields.next()
+    } else {
+        Some(first)
+    }
+}
+
+/// 域名黑名单中间件 - 直接在中间件管道里对解析后的查询名做匹配，按后缀语义
+/// 覆盖子域名（`example.com` 同时覆盖 `ads.example.com`），命中时短路返回
+/// NXDOMAIN/sinkhole 响应或直接丢弃；白名单优先于黑名单短路放行；规则文件
+/// 额外兼容 hosts 文件格式（`0.0.0.0 domain`）。
+///
+/// 规则集合放在 `RwLock` 里，后台任务按 `reload_interval` 定期从磁盘重新加载，
+/// 也可以调用 [`BlocklistMiddleware::reload`] 立即触发一次，不需要重启进程。
+pub struct BlocklistMiddleware {
+    enabled: bool,
+    action: BlocklistAction,
+    rules: Arc<RwLock<BlocklistRules>>,
+    blocked_counter: Option<Arc<AtomicU64>>,
+}
+
+impl BlocklistMiddleware {
+    pub async fn new(
+        enabled: bool,
+        rule_files: Vec<String>,
+        allowlist_files: Vec<String>,
+        action: BlocklistAction,
+        reload_interval: Duration,
+        blocked_counter: Option<Arc<AtomicU64>>,
+    ) -> Self {
+        let rules = Arc::new(RwLock::new(load_rules(&rule_files, &allowlist_files).await));
+
+        if enabled && !reload_interval.is_zero() {
+            spawn_reloader(rules.clone(), rule_files, allowlist_files, reload_interval);
+        }
+
+        Self {
+            enabled,
+            action,
+            rules,
+            blocked_counter,
+        }
+    }
+
+    /// 立即从磁盘重新加载规则文件和白名单文件
+    pub async fn reload(&self, rule_files: &[String], allowlist_files: &[String]) {
+        let fresh = load_rules(rule_files, allowlist_files).await;
+        *self.rules.write().await = fresh;
+        info!("blocklist 规则已手动热重载");
+    }
+
+    async fn matches(&self, name: &str) -> bool {
+        self.rules.read().await.matches(name)
+    }
+}
+
+/// 周期性从磁盘重新加载规则文件和白名单文件
+fn spawn_reloader(
+    rules: Arc<RwLock<BlocklistRules>>,
+    rule_files: Vec<String>,
+    allowlist_files: Vec<String>,
+    interval: Duration,
+) {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            let fresh = load_rules(&rule_files, &allowlist_files).await;
+            *rules.write().await = fresh;
+            debug!("blocklist 规则已定期热重载");
+        }
+    });
+}
+
+/// 为命中规则的查询合成响应：NXDOMAIN，或指向 0.0.0.0/:: 的 sinkhole 记录
+fn build_block_response(query: &Message, action: BlocklistAction) -> Option<Message> {
+    let question = query.questions.first()?;
+
+    let mut response = DnsPacket::new();
+    response.header.id = query.header.id;
+    response.header.response = true;
+    response.header.recursion_desired = query.header.recursion_desired;
+    response.header.recursion_available = true;
+    response.questions = query.questions.clone();
+
+    match action {
+        BlocklistAction::Drop => unreachable!("Drop 不走短路响应路径"),
+        BlocklistAction::NxDomain => {
+            response.header.set_result_code(ResultCode::NxDomain);
+        }
+        BlocklistAction::Sinkhole => {
+            response.header.set_result_code(ResultCode::NoError);
+            match question.qtype {
+                QueryType::A => response.answers.push(DnsRecord::A {
+                    domain: question.name.clone(),
+                    addr: "0.0.0.0".parse().unwrap(),
+                    ttl: 300,
+                }),
+                QueryType::Aaaa => response.answers.push(DnsRecord::Aaaa {
+                    domain: question.name.clone(),
+                    addr: "::".parse().unwrap(),
+                    ttl: 300,
+                }),
+                _ => {
+                    // 其它类型没有合理的 sinkhole 记录，退化为 NXDOMAIN
+                    response.header.set_result_code(ResultCode::NxDomain);
+                }
+            }
+        }
+    }
+
+    Some(response)
+}
+
+#[async_trait]
+impl Middleware for BlocklistMiddleware {
+    async fn handle_request(&self, request: &Message, _client_addr: SocketAddr) -> MiddlewareResult {
+        if !self.enabled {
+            return Ok(None);
+        }
+
+        let Some(question) = request.questions.first() else {
+            return Ok(None);
+        };
+        let name = question.name.to_lowercase();
+
+        if !self.matches(&name).await {
+            return Ok(None);
+        }
+
+        debug!("域名 {} 命中 blocklist 规则，拦截", name);
+        if let Some(counter) = &self.blocked_counter {
+            counter.fetch_add(1, Ordering::Relaxed);
+        }
+
+        if matches!(self.action, BlocklistAction::Drop) {
+            return Err(MiddlewareError::Blocked);
+        }
+
+        match build_block_response(request, self.action) {
+            Some(response) => Ok(Some(response)),
+            None => Err(MiddlewareError::Blocked),
+        }
+    }
+
+    async fn handle_response(
+        &self,
+        _request: &Message,
+        _response: &mut Message,
+        _client_addr: SocketAddr,
+    ) -> Result<(), MiddlewareError> {
+        Ok(())
+    }
+
+    fn name(&self) -> &str {
+        "BlocklistMiddleware"
+    }
+}
@@ -1,7 +1,8 @@
-use super::{Middleware, MiddlewareError, MiddlewareResult, DnsMessage};
+use super::{Middleware, MiddlewareError, MiddlewareResult};
+use crate::message::Message;
 use async_trait::async_trait;
 use std::net::SocketAddr;
-use tracing::{info, debug};
+use tracing::{debug, info};
 
 /// 日志中间件 - 记录所有DNS请求和响应
 pub struct LoggingMiddleware {
@@ -14,29 +15,42 @@ impl LoggingMiddleware {
     }
 }
 
+/// 取第一个问题的 "qname qtype" 用于日志展示，没有问题则退化为 "-"
+fn describe_question(message: &Message) -> String {
+    match message.questions.first() {
+        Some(q) => format!("{} {:?}", q.name, q.qtype),
+        None => "-".to_string(),
+    }
+}
+
 #[async_trait]
 impl Middleware for LoggingMiddleware {
-    async fn handle_request(
-        &self,
-        request: &DnsMessage,
-        client_addr: SocketAddr,
-    ) -> MiddlewareResult {
+    async fn handle_request(&self, request: &Message, client_addr: SocketAddr) -> MiddlewareResult {
         if self.enabled {
-            info!("DNS请求来自: {}, 大小: {} bytes", client_addr, request.len());
-            debug!("请求内容: {:?}", request);
+            info!(
+                "DNS请求来自: {}, 查询: {}",
+                client_addr,
+                describe_question(request)
+            );
+            debug!("请求报文: {:?}", request);
         }
         Ok(None) // 继续处理，不直接返回响应
     }
 
     async fn handle_response(
         &self,
-        _request: &DnsMessage,
-        response: &mut DnsMessage,
+        request: &Message,
+        response: &mut Message,
         client_addr: SocketAddr,
     ) -> Result<(), MiddlewareError> {
         if self.enabled {
-            info!("DNS响应发送给: {}, 大小: {} bytes", client_addr, response.len());
-            debug!("响应内容: {:?}", response);
+            info!(
+                "DNS响应发送给: {}, 查询: {}, 结果: {:?}",
+                client_addr,
+                describe_question(request),
+                response.header.result_code()
+            );
+            debug!("响应报文: {:?}", response);
         }
         Ok(())
     }
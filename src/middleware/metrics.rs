@@ -1,17 +1,68 @@
-use super::{Middleware, MiddlewareError, MiddlewareResult, DnsMessage};
+use super::{Middleware, MiddlewareError, MiddlewareResult};
+use crate::message::Message;
+use crate::protocol::{QueryType, ResultCode};
 use async_trait::async_trait;
+use serde::Serialize;
+use std::collections::HashMap;
 use std::net::SocketAddr;
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::RwLock;
 use tracing::info;
 
+/// 延迟直方图的桶边界（单位：秒），以 Prometheus 客户端库的默认档位为基础，
+/// 多了 1ms 一档，去掉了 10s 一档
+const LATENCY_BUCKETS_SECONDS: &[f64] = &[
+    0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0,
+];
+
+/// 上游解析延迟的累积直方图：`bucket_counts[i]` 是延迟 <= `LATENCY_BUCKETS_SECONDS[i]`
+/// 的观测次数，符合 Prometheus histogram 的累积语义
+#[derive(Debug, Default, Clone)]
+struct LatencyHistogram {
+    bucket_counts: Vec<u64>,
+    sum_seconds: f64,
+    count: u64,
+}
+
+impl LatencyHistogram {
+    fn new() -> Self {
+        Self {
+            bucket_counts: vec![0; LATENCY_BUCKETS_SECONDS.len()],
+            sum_seconds: 0.0,
+            count: 0,
+        }
+    }
+
+    fn observe(&mut self, duration: Duration) {
+        let seconds = duration.as_secs_f64();
+        for (i, &le) in LATENCY_BUCKETS_SECONDS.iter().enumerate() {
+            if seconds <= le {
+                self.bucket_counts[i] += 1;
+            }
+        }
+        self.sum_seconds += seconds;
+        self.count += 1;
+    }
+}
+
 /// 指标收集中间件 - 收集DNS服务器的统计信息
+///
+/// 除了基础的请求/响应计数外，还按查询类型、响应码分类计数，跟踪缓存命中率，
+/// 并以直方图记录上游解析延迟。缓存命中/未命中和延迟需要由 `DnsServer::run`
+/// 在恰当的时机调用对应的 `record_*` 方法喂入，因为只有调用方知道这些结果。
 pub struct MetricsMiddleware {
     enabled: bool,
     total_requests: Arc<AtomicU64>,
     total_responses: Arc<AtomicU64>,
     blocked_requests: Arc<AtomicU64>,
     rate_limited_requests: Arc<AtomicU64>,
+    cache_hits: Arc<AtomicU64>,
+    cache_misses: Arc<AtomicU64>,
+    query_type_counts: Arc<RwLock<HashMap<QueryType, u64>>>,
+    rcode_counts: Arc<RwLock<HashMap<ResultCode, u64>>>,
+    upstream_latency: Arc<RwLock<LatencyHistogram>>,
 }
 
 impl MetricsMiddleware {
@@ -22,7 +73,61 @@ impl MetricsMiddleware {
             total_responses: Arc::new(AtomicU64::new(0)),
             blocked_requests: Arc::new(AtomicU64::new(0)),
             rate_limited_requests: Arc::new(AtomicU64::new(0)),
+            cache_hits: Arc::new(AtomicU64::new(0)),
+            cache_misses: Arc::new(AtomicU64::new(0)),
+            query_type_counts: Arc::new(RwLock::new(HashMap::new())),
+            rcode_counts: Arc::new(RwLock::new(HashMap::new())),
+            upstream_latency: Arc::new(RwLock::new(LatencyHistogram::new())),
+        }
+    }
+
+    /// 共享阻止计数器的句柄，供其它中间件（如 `BlocklistMiddleware`）在拦截
+    /// 查询时直接自增，而不必把统计逻辑耦合进管道本身
+    pub fn blocked_counter(&self) -> Arc<AtomicU64> {
+        self.blocked_requests.clone()
+    }
+
+    /// 共享限流计数器的句柄，供 `RateLimitMiddleware` 在拒绝查询时直接自增
+    pub fn rate_limited_counter(&self) -> Arc<AtomicU64> {
+        self.rate_limited_requests.clone()
+    }
+
+    /// 记录一次查询的 qtype，供 `DnsServer::run`/`process_query` 在解析完查询后调用
+    pub async fn record_query_type(&self, qtype: QueryType) {
+        if !self.enabled {
+            return;
+        }
+        *self.query_type_counts.write().await.entry(qtype).or_insert(0) += 1;
+    }
+
+    /// 记录一次响应的 rcode
+    pub async fn record_rcode(&self, rcode: ResultCode) {
+        if !self.enabled {
+            return;
+        }
+        *self.rcode_counts.write().await.entry(rcode).or_insert(0) += 1;
+    }
+
+    /// 记录一次缓存命中，由 `DnsServer::run` 在命中缓存时调用
+    pub fn record_cache_hit(&self) {
+        if self.enabled {
+            self.cache_hits.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// 记录一次缓存未命中
+    pub fn record_cache_miss(&self) {
+        if self.enabled {
+            self.cache_misses.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// 记录一次上游解析耗时，由 `DnsServer::run` 在拿到（或未拿到）上游响应后调用
+    pub async fn record_upstream_latency(&self, duration: Duration) {
+        if !self.enabled {
+            return;
         }
+        self.upstream_latency.write().await.observe(duration);
     }
 
     pub fn get_metrics(&self) -> MetricsSnapshot {
@@ -31,6 +136,8 @@ impl MetricsMiddleware {
             total_responses: self.total_responses.load(Ordering::Relaxed),
             blocked_requests: self.blocked_requests.load(Ordering::Relaxed),
             rate_limited_requests: self.rate_limited_requests.load(Ordering::Relaxed),
+            cache_hits: self.cache_hits.load(Ordering::Relaxed),
+            cache_misses: self.cache_misses.load(Ordering::Relaxed),
         }
     }
 
@@ -41,25 +148,141 @@ impl MetricsMiddleware {
         info!("总响应数: {}", metrics.total_responses);
         info!("被阻止请求数: {}", metrics.blocked_requests);
         info!("被限流请求数: {}", metrics.rate_limited_requests);
+        info!("缓存命中: {}, 未命中: {}", metrics.cache_hits, metrics.cache_misses);
         info!("==================");
     }
+
+    /// 把所有计数器渲染成 Prometheus 文本暴露格式，供抓取端点使用
+    pub async fn render_prometheus(&self) -> String {
+        let snapshot = self.get_metrics();
+        let mut out = String::new();
+
+        push_counter(
+            &mut out,
+            "dns_requests_total",
+            "收到的 DNS 请求总数",
+            snapshot.total_requests,
+        );
+        push_counter(
+            &mut out,
+            "dns_responses_total",
+            "发出的 DNS 响应总数",
+            snapshot.total_responses,
+        );
+        push_counter(
+            &mut out,
+            "dns_blocked_requests_total",
+            "被过滤器/黑名单拦截的请求数",
+            snapshot.blocked_requests,
+        );
+        push_counter(
+            &mut out,
+            "dns_rate_limited_requests_total",
+            "被限流拒绝的请求数",
+            snapshot.rate_limited_requests,
+        );
+        push_counter(&mut out, "dns_cache_hits_total", "缓存命中次数", snapshot.cache_hits);
+        push_counter(
+            &mut out,
+            "dns_cache_misses_total",
+            "缓存未命中次数",
+            snapshot.cache_misses,
+        );
+
+        out.push_str("# HELP dns_queries_by_type_total 按查询类型分类的请求数\n");
+        out.push_str("# TYPE dns_queries_by_type_total counter\n");
+        let query_type_counts = self.query_type_counts.read().await;
+        let mut query_types: Vec<_> = query_type_counts.iter().collect();
+        query_types.sort_by_key(|(qtype, _)| query_type_label(**qtype));
+        for (qtype, count) in query_types {
+            out.push_str(&format!(
+                "dns_queries_by_type_total{{qtype=\"{}\"}} {}\n",
+                query_type_label(*qtype),
+                count
+            ));
+        }
+        out.push('\n');
+
+        out.push_str("# HELP dns_responses_by_rcode_total 按响应码分类的响应数\n");
+        out.push_str("# TYPE dns_responses_by_rcode_total counter\n");
+        let rcode_counts = self.rcode_counts.read().await;
+        let mut rcodes: Vec<_> = rcode_counts.iter().collect();
+        rcodes.sort_by_key(|(rcode, _)| rcode_label(**rcode));
+        for (rcode, count) in rcodes {
+            out.push_str(&format!(
+                "dns_responses_by_rcode_total{{rcode=\"{}\"}} {}\n",
+                rcode_label(*rcode),
+                count
+            ));
+        }
+        out.push('\n');
+
+        let latency = self.upstream_latency.read().await.clone();
+        out.push_str("# HELP dns_upstream_latency_seconds 上游解析耗时\n");
+        out.push_str("# TYPE dns_upstream_latency_seconds histogram\n");
+        for (le, count) in LATENCY_BUCKETS_SECONDS.iter().zip(latency.bucket_counts.iter()) {
+            out.push_str(&format!(
+                "dns_upstream_latency_seconds_bucket{{le=\"{}\"}} {}\n",
+                le, count
+            ));
+        }
+        out.push_str(&format!(
+            "dns_upstream_latency_seconds_bucket{{le=\"+Inf\"}} {}\n",
+            latency.count
+        ));
+        out.push_str(&format!(
+            "dns_upstream_latency_seconds_sum {}\n",
+            latency.sum_seconds
+        ));
+        out.push_str(&format!("dns_upstream_latency_seconds_count {}\n", latency.count));
+
+        out
+    }
+}
+
+fn push_counter(out: &mut String, name: &str, help: &str, value: u64) {
+    out.push_str(&format!("# HELP {} {}\n", name, help));
+    out.push_str(&format!("# TYPE {} counter\n", name));
+    out.push_str(&format!("{} {}\n\n", name, value));
+}
+
+fn query_type_label(qtype: QueryType) -> String {
+    match qtype {
+        QueryType::A => "A".to_string(),
+        QueryType::Ns => "NS".to_string(),
+        QueryType::Cname => "CNAME".to_string(),
+        QueryType::Soa => "SOA".to_string(),
+        QueryType::Mx => "MX".to_string(),
+        QueryType::Txt => "TXT".to_string(),
+        QueryType::Aaaa => "AAAA".to_string(),
+        QueryType::Unknown(n) => format!("TYPE{}", n),
+    }
+}
+
+fn rcode_label(rcode: ResultCode) -> &'static str {
+    match rcode {
+        ResultCode::NoError => "NOERROR",
+        ResultCode::FormErr => "FORMERR",
+        ResultCode::ServFail => "SERVFAIL",
+        ResultCode::NxDomain => "NXDOMAIN",
+        ResultCode::NotImp => "NOTIMP",
+        ResultCode::Refused => "REFUSED",
+    }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct MetricsSnapshot {
     pub total_requests: u64,
     pub total_responses: u64,
     pub blocked_requests: u64,
     pub rate_limited_requests: u64,
+    pub cache_hits: u64,
+    pub cache_misses: u64,
 }
 
 #[async_trait]
 impl Middleware for MetricsMiddleware {
-    async fn handle_request(
-        &self,
-        _request: &DnsMessage,
-        _client_addr: SocketAddr,
-    ) -> MiddlewareResult {
+    async fn handle_request(&self, _request: &Message, _client_addr: SocketAddr) -> MiddlewareResult {
         if self.enabled {
             self.total_requests.fetch_add(1, Ordering::Relaxed);
         }
@@ -68,8 +291,8 @@ impl Middleware for MetricsMiddleware {
 
     async fn handle_response(
         &self,
-        _request: &DnsMessage,
-        _response: &mut DnsMessage,
+        _request: &Message,
+        _response: &mut Message,
         _client_addr: SocketAddr,
     ) -> Result<(), MiddlewareError> {
         if self.enabled {
@@ -82,3 +305,25 @@ impl Middleware for MetricsMiddleware {
         "MetricsMiddleware"
     }
 }
+
+/// 让 `Arc<MetricsMiddleware>` 本身可以注册进管道，这样同一个实例既能统计
+/// 请求/响应，也能在 `DnsServer` 里被其它地方（HTTP 端点、`process_query`）共享
+#[async_trait]
+impl Middleware for Arc<MetricsMiddleware> {
+    async fn handle_request(&self, request: &Message, client_addr: SocketAddr) -> MiddlewareResult {
+        <MetricsMiddleware as Middleware>::handle_request(self, request, client_addr).await
+    }
+
+    async fn handle_response(
+        &self,
+        request: &Message,
+        response: &mut Message,
+        client_addr: SocketAddr,
+    ) -> Result<(), MiddlewareError> {
+        <MetricsMiddleware as Middleware>::handle_response(self, request, response, client_addr).await
+    }
+
+    fn name(&self) -> &str {
+        <MetricsMiddleware as Middleware>::name(self)
+    }
+}
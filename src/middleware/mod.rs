@@ -1,15 +1,14 @@
+pub mod blocklist;
 pub mod logging;
 pub mod metrics;
 pub mod rate_limit;
 
+use crate::message::Message;
 use async_trait::async_trait;
 use std::net::SocketAddr;
 use tracing::error;
 
-// 临时使用基础类型，后续替换为hickory-dns的实际类型
-pub type DnsMessage = Vec<u8>;
-
-pub type MiddlewareResult = Result<Option<DnsMessage>, MiddlewareError>;
+pub type MiddlewareResult = Result<Option<Message>, MiddlewareError>;
 
 #[derive(Debug)]
 pub enum MiddlewareError {
@@ -30,21 +29,17 @@ impl std::fmt::Display for MiddlewareError {
 
 impl std::error::Error for MiddlewareError {}
 
-/// 中间件trait - 处理DNS请求的中间件
+/// 中间件trait - 处理解析后的 DNS 消息，可以依据实际的问题/记录内容做决策
 #[async_trait]
 pub trait Middleware: Send + Sync {
     /// 处理DNS请求，返回None表示继续处理，返回Some(Message)表示直接返回响应
-    async fn handle_request(
-        &self,
-        request: &DnsMessage,
-        client_addr: SocketAddr,
-    ) -> MiddlewareResult;
+    async fn handle_request(&self, request: &Message, client_addr: SocketAddr) -> MiddlewareResult;
 
     /// 处理DNS响应
     async fn handle_response(
         &self,
-        request: &DnsMessage,
-        response: &mut DnsMessage,
+        request: &Message,
+        response: &mut Message,
         client_addr: SocketAddr,
     ) -> Result<(), MiddlewareError>;
 
@@ -69,11 +64,7 @@ impl MiddlewarePipeline {
     }
 
     /// 处理请求 - 如果任何中间件返回响应，则直接返回
-    pub async fn handle_request(
-        &self,
-        request: &DnsMessage,
-        client_addr: SocketAddr,
-    ) -> MiddlewareResult {
+    pub async fn handle_request(&self, request: &Message, client_addr: SocketAddr) -> MiddlewareResult {
         for middleware in &self.middlewares {
             match middleware.handle_request(request, client_addr).await {
                 Ok(Some(response)) => return Ok(Some(response)),
@@ -90,8 +81,8 @@ impl MiddlewarePipeline {
     /// 处理响应 - 所有中间件都会处理响应
     pub async fn handle_response(
         &self,
-        request: &DnsMessage,
-        response: &mut DnsMessage,
+        request: &Message,
+        response: &mut Message,
         client_addr: SocketAddr,
     ) -> Result<(), MiddlewareError> {
         for middleware in &self.middlewares {
@@ -1,9 +1,12 @@
-use super::{Middleware, MiddlewareError, MiddlewareResult, DnsMessage};
+use super::{Middleware, MiddlewareError, MiddlewareResult};
+use crate::message::Message;
 use async_trait::async_trait;
-use std::collections::HashMap;
-use std::net::SocketAddr;
+use lru::LruCache;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 use tokio::sync::Mutex;
 use tracing::warn;
 
@@ -40,7 +43,7 @@ impl RateLimitBucket {
         let now = Instant::now();
         let elapsed = now.duration_since(self.last_refill);
         let tokens_to_add = (elapsed.as_secs_f64() * self.refill_rate as f64) as u32;
-        
+
         if tokens_to_add > 0 {
             self.tokens = (self.tokens + tokens_to_add).min(self.max_tokens);
             self.last_refill = now;
@@ -48,21 +51,82 @@ impl RateLimitBucket {
     }
 }
 
-/// 限流中间件 - 基于客户端IP进行限流
+/// 把客户端地址按配置的前缀长度聚合，丢弃端口和地址中超出前缀的部分，
+/// 这样同一子网内更换源端口/地址的请求会打到同一个限流桶上
+fn aggregate_addr(addr: SocketAddr, ipv4_prefix_len: u8, ipv6_prefix_len: u8) -> IpAddr {
+    match addr.ip() {
+        IpAddr::V4(v4) => IpAddr::V4(mask_ipv4(v4, ipv4_prefix_len)),
+        IpAddr::V6(v6) => IpAddr::V6(mask_ipv6(v6, ipv6_prefix_len)),
+    }
+}
+
+fn mask_ipv4(addr: Ipv4Addr, prefix_len: u8) -> Ipv4Addr {
+    let bits = u32::from(addr);
+    let mask = if prefix_len == 0 {
+        // `prefix_len == 0` 的意思是把所有客户端聚合成一个桶，掩码应该是全 0；
+        // `32 - 0 = 32` 等于 u32 的位宽，移位会 panic（debug）/ 得到错误的全 1（release）
+        0
+    } else if prefix_len >= 32 {
+        u32::MAX
+    } else {
+        !0u32 << (32 - prefix_len)
+    };
+    Ipv4Addr::from(bits & mask)
+}
+
+fn mask_ipv6(addr: Ipv6Addr, prefix_len: u8) -> Ipv6Addr {
+    let bits = u128::from(addr);
+    let mask = if prefix_len == 0 {
+        0
+    } else if prefix_len >= 128 {
+        u128::MAX
+    } else {
+        !0u128 << (128 - prefix_len)
+    };
+    Ipv6Addr::from(bits & mask)
+}
+
+/// 限流中间件 - 基于客户端IP（按配置的前缀长度聚合）进行限流
+///
+/// 限流桶表使用有界 LRU 而不是无限增长的 `HashMap`：命中上限时淘汰最久未访问
+/// 的客户端，同时有一个后台任务定期清理长时间空闲的桶，双重防止伪造源地址的
+/// 洪泛把桶表撑爆造成内存耗尽。
 pub struct RateLimitMiddleware {
     enabled: bool,
-    buckets: Arc<Mutex<HashMap<SocketAddr, RateLimitBucket>>>,
+    buckets: Arc<Mutex<LruCache<IpAddr, RateLimitBucket>>>,
     max_tokens: u32,
     refill_rate: u32,
+    ipv4_prefix_len: u8,
+    ipv6_prefix_len: u8,
+    rate_limited_counter: Option<Arc<AtomicU64>>,
 }
 
 impl RateLimitMiddleware {
-    pub fn new(enabled: bool, requests_per_second: u32, burst_size: u32) -> Self {
+    pub fn new(
+        enabled: bool,
+        requests_per_second: u32,
+        burst_size: u32,
+        max_tracked_clients: usize,
+        idle_timeout: Duration,
+        ipv4_prefix_len: u8,
+        ipv6_prefix_len: u8,
+        rate_limited_counter: Option<Arc<AtomicU64>>,
+    ) -> Self {
+        let capacity = NonZeroUsize::new(max_tracked_clients).unwrap_or(NonZeroUsize::new(1).unwrap());
+        let buckets = Arc::new(Mutex::new(LruCache::new(capacity)));
+
+        if enabled {
+            spawn_idle_evictor(buckets.clone(), idle_timeout);
+        }
+
         Self {
             enabled,
-            buckets: Arc::new(Mutex::new(HashMap::new())),
+            buckets,
             max_tokens: burst_size,
             refill_rate: requests_per_second,
+            ipv4_prefix_len,
+            ipv6_prefix_len,
+            rate_limited_counter,
         }
     }
 
@@ -71,24 +135,42 @@ impl RateLimitMiddleware {
             return true;
         }
 
+        let key = aggregate_addr(client_addr, self.ipv4_prefix_len, self.ipv6_prefix_len);
         let mut buckets = self.buckets.lock().await;
         let bucket = buckets
-            .entry(client_addr)
-            .or_insert_with(|| RateLimitBucket::new(self.max_tokens, self.refill_rate));
-        
+            .get_or_insert_mut(key, || RateLimitBucket::new(self.max_tokens, self.refill_rate));
+
         bucket.try_consume()
     }
 }
 
+/// 周期性扫描桶表，清理 `last_refill` 早于 `idle_timeout` 的客户端
+fn spawn_idle_evictor(buckets: Arc<Mutex<LruCache<IpAddr, RateLimitBucket>>>, idle_timeout: Duration) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(idle_timeout);
+        loop {
+            interval.tick().await;
+            let mut buckets = buckets.lock().await;
+            let stale: Vec<IpAddr> = buckets
+                .iter()
+                .filter(|(_, bucket)| bucket.last_refill.elapsed() >= idle_timeout)
+                .map(|(addr, _)| *addr)
+                .collect();
+            for addr in stale {
+                buckets.pop(&addr);
+            }
+        }
+    });
+}
+
 #[async_trait]
 impl Middleware for RateLimitMiddleware {
-    async fn handle_request(
-        &self,
-        _request: &DnsMessage,
-        client_addr: SocketAddr,
-    ) -> MiddlewareResult {
+    async fn handle_request(&self, _request: &Message, client_addr: SocketAddr) -> MiddlewareResult {
         if !self.check_rate_limit(client_addr).await {
             warn!("客户端 {} 请求被限流", client_addr);
+            if let Some(counter) = &self.rate_limited_counter {
+                counter.fetch_add(1, Ordering::Relaxed);
+            }
             return Err(MiddlewareError::RateLimited);
         }
         Ok(None) // 继续处理
@@ -96,8 +178,8 @@ impl Middleware for RateLimitMiddleware {
 
     async fn handle_response(
         &self,
-        _request: &DnsMessage,
-        _response: &mut DnsMessage,
+        _request: &Message,
+        _response: &mut Message,
         _client_addr: SocketAddr,
     ) -> Result<(), MiddlewareError> {
         Ok(())
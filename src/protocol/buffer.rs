@@ -0,0 +1,360 @@
+use super::{ProtocolError, ProtocolResult};
+use std::collections::HashMap;
+
+/// 域名压缩指针最多允许跳转的次数，超过视为指针环，拒绝继续解析
+const MAX_JUMPS: usize = 5;
+
+/// 所有 DNS 包缓冲区共享的读写操作
+///
+/// `DnsHeader`/`DnsQuestion`/`DnsRecord` 的编解码只依赖这组接口，因此同一套代码
+/// 既可以作用于只读的 [`BytePacketBuffer`]（解析收到的查询/应答），也可以作用于
+/// 可增长的 [`VectorPacketBuffer`]（重新序列化时顺带做域名压缩）。
+pub trait PacketBuffer {
+    /// 读取当前位置的一个字节并前进
+    fn read(&mut self) -> ProtocolResult<u8>;
+
+    /// 读取指定位置的一个字节，不移动读写位置
+    fn get(&self, pos: usize) -> ProtocolResult<u8>;
+
+    /// 读取 `[start, start+len)` 范围的字节切片
+    fn get_range(&self, start: usize, len: usize) -> ProtocolResult<&[u8]>;
+
+    /// 当前读写位置
+    fn pos(&self) -> usize;
+
+    /// 将读写位置跳转到 `pos`
+    fn seek(&mut self, pos: usize);
+
+    /// 将读写位置前移 `steps` 字节
+    fn step(&mut self, steps: usize) -> ProtocolResult<()> {
+        self.seek(self.pos() + steps);
+        Ok(())
+    }
+
+    /// 追加写入一个字节
+    fn write_u8(&mut self, val: u8) -> ProtocolResult<()>;
+
+    /// 覆盖写入指定位置的一个字节，用于回填之前预留的长度字段
+    fn set(&mut self, pos: usize, val: u8) -> ProtocolResult<()>;
+
+    fn read_u16(&mut self) -> ProtocolResult<u16> {
+        let hi = self.read()? as u16;
+        let lo = self.read()? as u16;
+        Ok((hi << 8) | lo)
+    }
+
+    fn read_u32(&mut self) -> ProtocolResult<u32> {
+        let b0 = self.read()? as u32;
+        let b1 = self.read()? as u32;
+        let b2 = self.read()? as u32;
+        let b3 = self.read()? as u32;
+        Ok((b0 << 24) | (b1 << 16) | (b2 << 8) | b3)
+    }
+
+    fn write_u16(&mut self, val: u16) -> ProtocolResult<()> {
+        self.write_u8((val >> 8) as u8)?;
+        self.write_u8((val & 0xFF) as u8)
+    }
+
+    fn write_u32(&mut self, val: u32) -> ProtocolResult<()> {
+        self.write_u8((val >> 24) as u8)?;
+        self.write_u8((val >> 16) as u8)?;
+        self.write_u8((val >> 8) as u8)?;
+        self.write_u8((val & 0xFF) as u8)
+    }
+
+    fn set_u16(&mut self, pos: usize, val: u16) -> ProtocolResult<()> {
+        self.set(pos, (val >> 8) as u8)?;
+        self.set(pos + 1, (val & 0xFF) as u8)
+    }
+
+    /// 读取一个（可能包含压缩指针的）域名标签序列，返回小写归一化前的原始域名
+    ///
+    /// 标签以长度字节前缀，`0x00` 长度表示结尾；若长度字节的高两位都是 1
+    /// (`0xC0` 掩码)，则它和下一个字节组成一个指向包内绝对偏移的压缩指针，
+    /// 读取位置需要跳转过去继续读，但最终返回的读写位置只前进到指针本身之后。
+    fn read_qname(&mut self) -> ProtocolResult<String> {
+        let mut pos = self.pos();
+        let mut jumped = false;
+        let mut jumps_performed = 0;
+
+        let mut delim = "";
+        let mut outstr = String::new();
+
+        loop {
+            if jumps_performed > MAX_JUMPS {
+                return Err(ProtocolError::TooManyJumps);
+            }
+
+            let len = self.get(pos)?;
+
+            // 压缩指针：高两位为 1
+            if (len & 0xC0) == 0xC0 {
+                // 第一次跳转时把读写位置留在指针之后，后续跳转不再移动它
+                if !jumped {
+                    self.seek(pos + 2);
+                }
+
+                let b2 = self.get(pos + 1)? as u16;
+                let offset = (((len as u16) ^ 0xC0) << 8) | b2;
+                pos = offset as usize;
+
+                jumped = true;
+                jumps_performed += 1;
+                continue;
+            }
+
+            // 长度字节高两位必须是 00，否则是非法标签
+            if (len & 0xC0) != 0 {
+                return Err(ProtocolError::InvalidLabelLength(len));
+            }
+
+            pos += 1;
+            if len == 0 {
+                break;
+            }
+
+            outstr.push_str(delim);
+            let label = self.get_range(pos, len as usize)?;
+            outstr.push_str(&String::from_utf8_lossy(label));
+
+            delim = ".";
+            pos += len as usize;
+        }
+
+        if !jumped {
+            self.seek(pos);
+        }
+
+        Ok(outstr)
+    }
+
+    /// 写入一个域名，不做压缩（[`VectorPacketBuffer::write_qname`] 会覆盖此方法以支持压缩）
+    fn write_qname(&mut self, qname: &str) -> ProtocolResult<()> {
+        for label in qname.split('.').filter(|l| !l.is_empty()) {
+            let len = label.len();
+            if len > 0x3F {
+                return Err(ProtocolError::InvalidLabelLength(len as u8));
+            }
+            self.write_u8(len as u8)?;
+            for b in label.as_bytes() {
+                self.write_u8(*b)?;
+            }
+        }
+        self.write_u8(0)
+    }
+}
+
+/// 只读的包缓冲区，用于解析收到的查询/应答报文
+pub struct BytePacketBuffer {
+    buf: Vec<u8>,
+    pos: usize,
+}
+
+impl BytePacketBuffer {
+    pub fn new(data: &[u8]) -> Self {
+        Self {
+            buf: data.to_vec(),
+            pos: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.buf.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buf.is_empty()
+    }
+}
+
+impl PacketBuffer for BytePacketBuffer {
+    fn read(&mut self) -> ProtocolResult<u8> {
+        let val = self.get(self.pos)?;
+        self.pos += 1;
+        Ok(val)
+    }
+
+    fn get(&self, pos: usize) -> ProtocolResult<u8> {
+        self.buf.get(pos).copied().ok_or(ProtocolError::EndOfBuffer)
+    }
+
+    fn get_range(&self, start: usize, len: usize) -> ProtocolResult<&[u8]> {
+        let end = start.checked_add(len).ok_or(ProtocolError::EndOfBuffer)?;
+        if end > self.buf.len() {
+            return Err(ProtocolError::EndOfBuffer);
+        }
+        Ok(&self.buf[start..end])
+    }
+
+    fn pos(&self) -> usize {
+        self.pos
+    }
+
+    fn seek(&mut self, pos: usize) {
+        self.pos = pos;
+    }
+
+    fn write_u8(&mut self, val: u8) -> ProtocolResult<()> {
+        if self.pos < self.buf.len() {
+            self.buf[self.pos] = val;
+        } else {
+            self.buf.push(val);
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    fn set(&mut self, pos: usize, val: u8) -> ProtocolResult<()> {
+        if pos >= self.buf.len() {
+            return Err(ProtocolError::EndOfBuffer);
+        }
+        self.buf[pos] = val;
+        Ok(())
+    }
+}
+
+/// 可增长的写缓冲区，重新序列化包时使用；维护一张“域名 -> 首次出现偏移”的表，
+/// 以便对后续重复出现的域名后缀写入压缩指针而不是完整标签。
+pub struct VectorPacketBuffer {
+    buf: Vec<u8>,
+    pos: usize,
+    label_lookup: HashMap<String, usize>,
+}
+
+impl VectorPacketBuffer {
+    pub fn new() -> Self {
+        Self {
+            buf: Vec::new(),
+            pos: 0,
+            label_lookup: HashMap::new(),
+        }
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.buf
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.buf
+    }
+}
+
+impl Default for VectorPacketBuffer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PacketBuffer for VectorPacketBuffer {
+    fn read(&mut self) -> ProtocolResult<u8> {
+        let val = self.get(self.pos)?;
+        self.pos += 1;
+        Ok(val)
+    }
+
+    fn get(&self, pos: usize) -> ProtocolResult<u8> {
+        self.buf.get(pos).copied().ok_or(ProtocolError::EndOfBuffer)
+    }
+
+    fn get_range(&self, start: usize, len: usize) -> ProtocolResult<&[u8]> {
+        let end = start.checked_add(len).ok_or(ProtocolError::EndOfBuffer)?;
+        if end > self.buf.len() {
+            return Err(ProtocolError::EndOfBuffer);
+        }
+        Ok(&self.buf[start..end])
+    }
+
+    fn pos(&self) -> usize {
+        self.pos
+    }
+
+    fn seek(&mut self, pos: usize) {
+        self.pos = pos;
+    }
+
+    fn write_u8(&mut self, val: u8) -> ProtocolResult<()> {
+        self.buf.push(val);
+        self.pos += 1;
+        Ok(())
+    }
+
+    fn set(&mut self, pos: usize, val: u8) -> ProtocolResult<()> {
+        if pos >= self.buf.len() {
+            return Err(ProtocolError::EndOfBuffer);
+        }
+        self.buf[pos] = val;
+        Ok(())
+    }
+
+    fn write_qname(&mut self, qname: &str) -> ProtocolResult<()> {
+        let labels: Vec<&str> = qname.split('.').filter(|l| !l.is_empty()).collect();
+
+        for (i, label) in labels.iter().enumerate() {
+            let suffix = labels[i..].join(".").to_lowercase();
+
+            if let Some(&pointer) = self.label_lookup.get(&suffix) {
+                // 已经写过这个后缀，写一个压缩指针代替剩余的标签
+                let pointer = pointer as u16;
+                self.write_u8(((pointer >> 8) as u8) | 0xC0)?;
+                self.write_u8((pointer & 0xFF) as u8)?;
+                return Ok(());
+            }
+
+            let start = self.pos();
+            if start <= 0x3FFF {
+                self.label_lookup.insert(suffix, start);
+            }
+
+            let len = label.len();
+            if len > 0x3F {
+                return Err(ProtocolError::InvalidLabelLength(len as u8));
+            }
+            self.write_u8(len as u8)?;
+            for b in label.as_bytes() {
+                self.write_u8(*b)?;
+            }
+        }
+        self.write_u8(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_qname_follows_compression_pointer() {
+        // "a.example.com" 后跟一个指向偏移 0 的压缩指针 "b.\xC0\x00"
+        let mut raw = vec![1, b'a', 7, b'e', b'x', b'a', b'm', b'p', b'l', b'e', 3, b'c', b'o', b'm', 0];
+        raw.extend_from_slice(&[1, b'b', 0xC0, 0]);
+
+        let mut buffer = BytePacketBuffer::new(&raw);
+        assert_eq!(buffer.read_qname().unwrap(), "a.example.com");
+
+        buffer.seek(15);
+        assert_eq!(buffer.read_qname().unwrap(), "b.a.example.com");
+    }
+
+    #[test]
+    fn read_qname_rejects_pointer_loop() {
+        // 偏移 0 处的压缩指针指向自身，必须在达到跳转上限后返回错误而不是死循环
+        let raw = vec![0xC0, 0x00];
+        let mut buffer = BytePacketBuffer::new(&raw);
+        assert!(matches!(
+            buffer.read_qname(),
+            Err(ProtocolError::TooManyJumps)
+        ));
+    }
+
+    #[test]
+    fn vector_buffer_compresses_repeated_names() {
+        let mut buffer = VectorPacketBuffer::new();
+        buffer.write_qname("example.com").unwrap();
+        let first_len = buffer.pos();
+
+        buffer.write_qname("example.com").unwrap();
+        // 第二次写入同一个域名应该只产生一个两字节的压缩指针
+        assert_eq!(buffer.pos(), first_len + 2);
+    }
+}
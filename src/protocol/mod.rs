@@ -0,0 +1,40 @@
+//! DNS 线格式(wire format)解析与序列化子系统
+//!
+//! 借鉴 hermes/Alfis 的实现思路：`PacketBuffer` 负责按字节读写并处理域名压缩指针，
+//! `DnsPacket` 在其之上解码/编码出结构化的 header、question 与 resource record。
+//! 这个模块取代了此前在 `utils.rs` 里对原始字节的零散处理，是缓存键计算、域名过滤、
+//! 权威区域应答等后续功能共同依赖的基础。
+
+pub mod buffer;
+pub mod packet;
+
+pub use buffer::{BytePacketBuffer, PacketBuffer, VectorPacketBuffer};
+pub use packet::{DnsHeader, DnsPacket, DnsQuestion, DnsRecord, QueryType, ResultCode};
+
+/// 解析/序列化过程中可能出现的错误
+#[derive(Debug)]
+pub enum ProtocolError {
+    /// 读写位置越过了缓冲区末尾
+    EndOfBuffer,
+    /// 域名压缩指针跳转次数超过上限，可能是构造出的指针环
+    TooManyJumps,
+    /// 标签长度字节不合法（例如声明了压缩指针但高位组合非法）
+    InvalidLabelLength(u8),
+    /// rdata 声明的长度超出了包的剩余字节数
+    RdataOverflow,
+}
+
+impl std::fmt::Display for ProtocolError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProtocolError::EndOfBuffer => write!(f, "读取/写入位置超出缓冲区范围"),
+            ProtocolError::TooManyJumps => write!(f, "域名压缩指针跳转次数过多，疑似指针环"),
+            ProtocolError::InvalidLabelLength(len) => write!(f, "非法的标签长度字节: {}", len),
+            ProtocolError::RdataOverflow => write!(f, "rdata 长度超出了包的剩余字节数"),
+        }
+    }
+}
+
+impl std::error::Error for ProtocolError {}
+
+pub type ProtocolResult<T> = Result<T, ProtocolError>;
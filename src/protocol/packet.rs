@@ -0,0 +1,742 @@
+use super::buffer::PacketBuffer;
+use super::{ProtocolError, ProtocolResult};
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+/// DNS 查询类型（只建模目前用得到的几种，其余归入 `Unknown`）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum QueryType {
+    A,
+    Ns,
+    Cname,
+    Soa,
+    Mx,
+    Txt,
+    Aaaa,
+    Unknown(u16),
+}
+
+impl QueryType {
+    pub fn to_num(self) -> u16 {
+        match self {
+            QueryType::A => 1,
+            QueryType::Ns => 2,
+            QueryType::Cname => 5,
+            QueryType::Soa => 6,
+            QueryType::Mx => 15,
+            QueryType::Txt => 16,
+            QueryType::Aaaa => 28,
+            QueryType::Unknown(n) => n,
+        }
+    }
+
+    pub fn from_num(num: u16) -> QueryType {
+        match num {
+            1 => QueryType::A,
+            2 => QueryType::Ns,
+            5 => QueryType::Cname,
+            6 => QueryType::Soa,
+            15 => QueryType::Mx,
+            16 => QueryType::Txt,
+            28 => QueryType::Aaaa,
+            _ => QueryType::Unknown(num),
+        }
+    }
+}
+
+/// DNS 响应码（RFC 1035 4.1.1）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ResultCode {
+    NoError,
+    FormErr,
+    ServFail,
+    NxDomain,
+    NotImp,
+    Refused,
+}
+
+impl ResultCode {
+    pub fn to_num(self) -> u8 {
+        match self {
+            ResultCode::NoError => 0,
+            ResultCode::FormErr => 1,
+            ResultCode::ServFail => 2,
+            ResultCode::NxDomain => 3,
+            ResultCode::NotImp => 4,
+            ResultCode::Refused => 5,
+        }
+    }
+
+    pub fn from_num(num: u8) -> ResultCode {
+        match num {
+            1 => ResultCode::FormErr,
+            2 => ResultCode::ServFail,
+            3 => ResultCode::NxDomain,
+            4 => ResultCode::NotImp,
+            5 => ResultCode::Refused,
+            _ => ResultCode::NoError,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DnsHeader {
+    pub id: u16,
+
+    pub recursion_desired: bool,
+    pub truncated_message: bool,
+    pub authoritative_answer: bool,
+    pub opcode: u8,
+    pub response: bool,
+
+    pub rescode: u8,
+    pub checking_disabled: bool,
+    pub authed_data: bool,
+    pub z: bool,
+    pub recursion_available: bool,
+
+    pub questions: u16,
+    pub answers: u16,
+    pub authoritative_entries: u16,
+    pub resource_entries: u16,
+}
+
+impl DnsHeader {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn result_code(&self) -> ResultCode {
+        ResultCode::from_num(self.rescode)
+    }
+
+    pub fn set_result_code(&mut self, code: ResultCode) {
+        self.rescode = code.to_num();
+    }
+
+    pub fn read(&mut self, buffer: &mut dyn PacketBuffer) -> ProtocolResult<()> {
+        self.id = buffer.read_u16()?;
+
+        let flags = buffer.read_u16()?;
+        let a = (flags >> 8) as u8;
+        let b = (flags & 0xFF) as u8;
+
+        self.recursion_desired = (a & 1) > 0;
+        self.truncated_message = (a & 2) > 0;
+        self.authoritative_answer = (a & 4) > 0;
+        self.opcode = (a >> 3) & 0x0F;
+        self.response = (a & 0x80) > 0;
+
+        self.rescode = b & 0x0F;
+        self.checking_disabled = (b & 0x10) > 0;
+        self.authed_data = (b & 0x20) > 0;
+        self.z = (b & 0x40) > 0;
+        self.recursion_available = (b & 0x80) > 0;
+
+        self.questions = buffer.read_u16()?;
+        self.answers = buffer.read_u16()?;
+        self.authoritative_entries = buffer.read_u16()?;
+        self.resource_entries = buffer.read_u16()?;
+
+        Ok(())
+    }
+
+    pub fn write(&self, buffer: &mut dyn PacketBuffer) -> ProtocolResult<()> {
+        buffer.write_u16(self.id)?;
+
+        let a = (self.recursion_desired as u8)
+            | ((self.truncated_message as u8) << 1)
+            | ((self.authoritative_answer as u8) << 2)
+            | (self.opcode << 3)
+            | ((self.response as u8) << 7);
+
+        let b = self.rescode
+            | ((self.checking_disabled as u8) << 4)
+            | ((self.authed_data as u8) << 5)
+            | ((self.z as u8) << 6)
+            | ((self.recursion_available as u8) << 7);
+
+        buffer.write_u8(a)?;
+        buffer.write_u8(b)?;
+
+        buffer.write_u16(self.questions)?;
+        buffer.write_u16(self.answers)?;
+        buffer.write_u16(self.authoritative_entries)?;
+        buffer.write_u16(self.resource_entries)?;
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DnsQuestion {
+    pub name: String,
+    pub qtype: QueryType,
+    pub qclass: u16,
+}
+
+impl DnsQuestion {
+    pub fn new(name: String, qtype: QueryType) -> Self {
+        Self {
+            name,
+            qtype,
+            qclass: 1, // IN
+        }
+    }
+
+    pub fn read(buffer: &mut dyn PacketBuffer) -> ProtocolResult<Self> {
+        let name = buffer.read_qname()?;
+        let qtype = QueryType::from_num(buffer.read_u16()?);
+        let qclass = buffer.read_u16()?;
+
+        Ok(Self { name, qtype, qclass })
+    }
+
+    pub fn write(&self, buffer: &mut dyn PacketBuffer) -> ProtocolResult<()> {
+        buffer.write_qname(&self.name)?;
+        buffer.write_u16(self.qtype.to_num())?;
+        buffer.write_u16(self.qclass)
+    }
+}
+
+/// 解析后的资源记录，按类型建模成枚举，`ttl` 字段统一提到每个变体上方便统计读取
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum DnsRecord {
+    A {
+        domain: String,
+        addr: Ipv4Addr,
+        ttl: u32,
+    },
+    Aaaa {
+        domain: String,
+        addr: Ipv6Addr,
+        ttl: u32,
+    },
+    Ns {
+        domain: String,
+        host: String,
+        ttl: u32,
+    },
+    Cname {
+        domain: String,
+        host: String,
+        ttl: u32,
+    },
+    Mx {
+        domain: String,
+        priority: u16,
+        host: String,
+        ttl: u32,
+    },
+    Soa {
+        domain: String,
+        m_name: String,
+        r_name: String,
+        serial: u32,
+        refresh: u32,
+        retry: u32,
+        expire: u32,
+        minimum: u32,
+        ttl: u32,
+    },
+    Txt {
+        domain: String,
+        /// 原始字节，不假设是合法 UTF-8：DKIM/SPF 等 TXT 记录常常携带二进制数据，
+        /// 之前用 `String::from_utf8_lossy` 存会把非法字节替换成 U+FFFD，
+        /// 再序列化时长度和内容都对不上了
+        data: Vec<u8>,
+        ttl: u32,
+    },
+    Unknown {
+        domain: String,
+        qtype: u16,
+        class: u16,
+        ttl: u32,
+        /// 原始 rdata 字节，原样写回；没有它就无法无损地转发未建模的记录类型
+        /// （EDNS OPT、SVCB/HTTPS、PTR、SRV、CAA……）
+        rdata: Vec<u8>,
+    },
+}
+
+impl DnsRecord {
+    pub fn domain(&self) -> &str {
+        match self {
+            DnsRecord::A { domain, .. }
+            | DnsRecord::Aaaa { domain, .. }
+            | DnsRecord::Ns { domain, .. }
+            | DnsRecord::Cname { domain, .. }
+            | DnsRecord::Mx { domain, .. }
+            | DnsRecord::Soa { domain, .. }
+            | DnsRecord::Txt { domain, .. }
+            | DnsRecord::Unknown { domain, .. } => domain,
+        }
+    }
+
+    pub fn ttl(&self) -> u32 {
+        match self {
+            DnsRecord::A { ttl, .. }
+            | DnsRecord::Aaaa { ttl, .. }
+            | DnsRecord::Ns { ttl, .. }
+            | DnsRecord::Cname { ttl, .. }
+            | DnsRecord::Mx { ttl, .. }
+            | DnsRecord::Soa { ttl, .. }
+            | DnsRecord::Txt { ttl, .. }
+            | DnsRecord::Unknown { ttl, .. } => *ttl,
+        }
+    }
+
+    pub fn set_ttl(&mut self, new_ttl: u32) {
+        match self {
+            DnsRecord::A { ttl, .. }
+            | DnsRecord::Aaaa { ttl, .. }
+            | DnsRecord::Ns { ttl, .. }
+            | DnsRecord::Cname { ttl, .. }
+            | DnsRecord::Mx { ttl, .. }
+            | DnsRecord::Soa { ttl, .. }
+            | DnsRecord::Txt { ttl, .. }
+            | DnsRecord::Unknown { ttl, .. } => *ttl = new_ttl,
+        }
+    }
+
+    pub fn query_type(&self) -> QueryType {
+        match self {
+            DnsRecord::A { .. } => QueryType::A,
+            DnsRecord::Aaaa { .. } => QueryType::Aaaa,
+            DnsRecord::Ns { .. } => QueryType::Ns,
+            DnsRecord::Cname { .. } => QueryType::Cname,
+            DnsRecord::Mx { .. } => QueryType::Mx,
+            DnsRecord::Soa { .. } => QueryType::Soa,
+            DnsRecord::Txt { .. } => QueryType::Txt,
+            DnsRecord::Unknown { qtype, .. } => QueryType::from_num(*qtype),
+        }
+    }
+
+    pub fn read(buffer: &mut dyn PacketBuffer) -> ProtocolResult<Self> {
+        let domain = buffer.read_qname()?;
+        let qtype_num = buffer.read_u16()?;
+        let qtype = QueryType::from_num(qtype_num);
+        let class = buffer.read_u16()?;
+        let ttl = buffer.read_u32()?;
+        let data_len = buffer.read_u16()?;
+
+        let rdata_start = buffer.pos();
+        let rdata_end = rdata_start + data_len as usize;
+
+        // 声明的 rdata 长度必须完全落在缓冲区内，提前校验而不是等某个类型的
+        // per-type 读取或之后的 seek 意外越界才发现
+        if buffer.get_range(rdata_start, data_len as usize).is_err() {
+            return Err(ProtocolError::RdataOverflow);
+        }
+
+        let record = match qtype {
+            QueryType::A => {
+                let raw = buffer.read_u32()?;
+                let addr = Ipv4Addr::new(
+                    ((raw >> 24) & 0xFF) as u8,
+                    ((raw >> 16) & 0xFF) as u8,
+                    ((raw >> 8) & 0xFF) as u8,
+                    (raw & 0xFF) as u8,
+                );
+                DnsRecord::A { domain, addr, ttl }
+            }
+            QueryType::Aaaa => {
+                let mut parts = [0u16; 8];
+                for part in parts.iter_mut() {
+                    *part = buffer.read_u16()?;
+                }
+                let addr = Ipv6Addr::new(
+                    parts[0], parts[1], parts[2], parts[3], parts[4], parts[5], parts[6], parts[7],
+                );
+                DnsRecord::Aaaa { domain, addr, ttl }
+            }
+            QueryType::Ns => {
+                let host = buffer.read_qname()?;
+                DnsRecord::Ns { domain, host, ttl }
+            }
+            QueryType::Cname => {
+                let host = buffer.read_qname()?;
+                DnsRecord::Cname { domain, host, ttl }
+            }
+            QueryType::Mx => {
+                let priority = buffer.read_u16()?;
+                let host = buffer.read_qname()?;
+                DnsRecord::Mx {
+                    domain,
+                    priority,
+                    host,
+                    ttl,
+                }
+            }
+            QueryType::Soa => {
+                let m_name = buffer.read_qname()?;
+                let r_name = buffer.read_qname()?;
+                let serial = buffer.read_u32()?;
+                let refresh = buffer.read_u32()?;
+                let retry = buffer.read_u32()?;
+                let expire = buffer.read_u32()?;
+                let minimum = buffer.read_u32()?;
+                DnsRecord::Soa {
+                    domain,
+                    m_name,
+                    r_name,
+                    serial,
+                    refresh,
+                    retry,
+                    expire,
+                    minimum,
+                    ttl,
+                }
+            }
+            QueryType::Txt => {
+                let len = rdata_end.saturating_sub(buffer.pos());
+                let data = buffer.get_range(buffer.pos(), len)?.to_vec();
+                buffer.step(len)?;
+                DnsRecord::Txt { domain, data, ttl }
+            }
+            QueryType::Unknown(_) => {
+                let rdata = buffer.get_range(rdata_start, data_len as usize)?.to_vec();
+                buffer.step(data_len as usize)?;
+                DnsRecord::Unknown {
+                    domain,
+                    qtype: qtype_num,
+                    class,
+                    ttl,
+                    rdata,
+                }
+            }
+        };
+
+        // rdata 内容的读取不能越过声明的 data_len 末尾，越过则认为包已损坏；
+        // 没有读完的剩余字节直接跳过
+        if buffer.pos() > rdata_end || buffer.pos() < rdata_start {
+            return Err(ProtocolError::RdataOverflow);
+        }
+        buffer.seek(rdata_end);
+
+        Ok(record)
+    }
+
+    pub fn write(&self, buffer: &mut dyn PacketBuffer) -> ProtocolResult<()> {
+        let start_pos = buffer.pos();
+
+        match self {
+            DnsRecord::A { domain, addr, ttl } => {
+                buffer.write_qname(domain)?;
+                buffer.write_u16(QueryType::A.to_num())?;
+                buffer.write_u16(1)?;
+                buffer.write_u32(*ttl)?;
+                buffer.write_u16(4)?;
+                for octet in addr.octets() {
+                    buffer.write_u8(octet)?;
+                }
+            }
+            DnsRecord::Aaaa { domain, addr, ttl } => {
+                buffer.write_qname(domain)?;
+                buffer.write_u16(QueryType::Aaaa.to_num())?;
+                buffer.write_u16(1)?;
+                buffer.write_u32(*ttl)?;
+                buffer.write_u16(16)?;
+                for segment in addr.segments() {
+                    buffer.write_u16(segment)?;
+                }
+            }
+            DnsRecord::Ns { domain, host, ttl } => {
+                write_name_record(buffer, domain, QueryType::Ns, host, *ttl)?;
+            }
+            DnsRecord::Cname { domain, host, ttl } => {
+                write_name_record(buffer, domain, QueryType::Cname, host, *ttl)?;
+            }
+            DnsRecord::Mx {
+                domain,
+                priority,
+                host,
+                ttl,
+            } => {
+                buffer.write_qname(domain)?;
+                buffer.write_u16(QueryType::Mx.to_num())?;
+                buffer.write_u16(1)?;
+                buffer.write_u32(*ttl)?;
+
+                let len_pos = buffer.pos();
+                buffer.write_u16(0)?;
+                buffer.write_u16(*priority)?;
+                buffer.write_qname(host)?;
+
+                let size = buffer.pos() - (len_pos + 2);
+                buffer.set_u16(len_pos, size as u16)?;
+            }
+            DnsRecord::Soa {
+                domain,
+                m_name,
+                r_name,
+                serial,
+                refresh,
+                retry,
+                expire,
+                minimum,
+                ttl,
+            } => {
+                buffer.write_qname(domain)?;
+                buffer.write_u16(QueryType::Soa.to_num())?;
+                buffer.write_u16(1)?;
+                buffer.write_u32(*ttl)?;
+
+                let len_pos = buffer.pos();
+                buffer.write_u16(0)?;
+                buffer.write_qname(m_name)?;
+                buffer.write_qname(r_name)?;
+                buffer.write_u32(*serial)?;
+                buffer.write_u32(*refresh)?;
+                buffer.write_u32(*retry)?;
+                buffer.write_u32(*expire)?;
+                buffer.write_u32(*minimum)?;
+
+                let size = buffer.pos() - (len_pos + 2);
+                buffer.set_u16(len_pos, size as u16)?;
+            }
+            DnsRecord::Txt { domain, data, ttl } => {
+                buffer.write_qname(domain)?;
+                buffer.write_u16(QueryType::Txt.to_num())?;
+                buffer.write_u16(1)?;
+                buffer.write_u32(*ttl)?;
+                buffer.write_u16(data.len() as u16)?;
+                for b in data {
+                    buffer.write_u8(*b)?;
+                }
+            }
+            DnsRecord::Unknown {
+                domain,
+                qtype,
+                class,
+                ttl,
+                rdata,
+            } => {
+                // 原样写回，保留原始 class（例如 EDNS OPT 记录把 class 挪用做 UDP 负载大小）
+                buffer.write_qname(domain)?;
+                buffer.write_u16(*qtype)?;
+                buffer.write_u16(*class)?;
+                buffer.write_u32(*ttl)?;
+                buffer.write_u16(rdata.len() as u16)?;
+                for b in rdata {
+                    buffer.write_u8(*b)?;
+                }
+            }
+        }
+
+        let _ = start_pos;
+        Ok(())
+    }
+}
+
+fn write_name_record(
+    buffer: &mut dyn PacketBuffer,
+    domain: &str,
+    qtype: QueryType,
+    host: &str,
+    ttl: u32,
+) -> ProtocolResult<()> {
+    buffer.write_qname(domain)?;
+    buffer.write_u16(qtype.to_num())?;
+    buffer.write_u16(1)?;
+    buffer.write_u32(ttl)?;
+
+    let len_pos = buffer.pos();
+    buffer.write_u16(0)?;
+    buffer.write_qname(host)?;
+
+    let size = buffer.pos() - (len_pos + 2);
+    buffer.set_u16(len_pos, size as u16)
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DnsPacket {
+    pub header: DnsHeader,
+    pub questions: Vec<DnsQuestion>,
+    pub answers: Vec<DnsRecord>,
+    pub authorities: Vec<DnsRecord>,
+    pub resources: Vec<DnsRecord>,
+}
+
+impl DnsPacket {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_buffer(buffer: &mut dyn PacketBuffer) -> ProtocolResult<Self> {
+        let mut result = Self::new();
+        result.header.read(buffer)?;
+
+        for _ in 0..result.header.questions {
+            result.questions.push(DnsQuestion::read(buffer)?);
+        }
+        for _ in 0..result.header.answers {
+            result.answers.push(DnsRecord::read(buffer)?);
+        }
+        for _ in 0..result.header.authoritative_entries {
+            result.authorities.push(DnsRecord::read(buffer)?);
+        }
+        for _ in 0..result.header.resource_entries {
+            result.resources.push(DnsRecord::read(buffer)?);
+        }
+
+        Ok(result)
+    }
+
+    pub fn from_bytes(data: &[u8]) -> ProtocolResult<Self> {
+        let mut buffer = super::buffer::BytePacketBuffer::new(data);
+        Self::from_buffer(&mut buffer)
+    }
+
+    pub fn write(&mut self, buffer: &mut dyn PacketBuffer) -> ProtocolResult<()> {
+        self.header.questions = self.questions.len() as u16;
+        self.header.answers = self.answers.len() as u16;
+        self.header.authoritative_entries = self.authorities.len() as u16;
+        self.header.resource_entries = self.resources.len() as u16;
+
+        self.header.write(buffer)?;
+
+        for question in &self.questions {
+            question.write(buffer)?;
+        }
+        for record in &self.answers {
+            record.write(buffer)?;
+        }
+        for record in &self.authorities {
+            record.write(buffer)?;
+        }
+        for record in &self.resources {
+            record.write(buffer)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn to_bytes(&mut self) -> ProtocolResult<Vec<u8>> {
+        let mut buffer = super::buffer::VectorPacketBuffer::new();
+        self.write(&mut buffer)?;
+        Ok(buffer.into_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::buffer::BytePacketBuffer;
+
+    #[test]
+    fn round_trips_a_record_query() {
+        let mut packet = DnsPacket::new();
+        packet.header.id = 0x1234;
+        packet.header.recursion_desired = true;
+        packet
+            .questions
+            .push(DnsQuestion::new("example.com".to_string(), QueryType::A));
+        packet.answers.push(DnsRecord::A {
+            domain: "example.com".to_string(),
+            addr: Ipv4Addr::new(93, 184, 216, 34),
+            ttl: 300,
+        });
+
+        let bytes = packet.to_bytes().unwrap();
+
+        let mut buffer = BytePacketBuffer::new(&bytes);
+        let parsed = DnsPacket::from_buffer(&mut buffer).unwrap();
+
+        assert_eq!(parsed.header.id, 0x1234);
+        assert_eq!(parsed.questions.len(), 1);
+        assert_eq!(parsed.questions[0].name, "example.com");
+        assert_eq!(parsed.answers.len(), 1);
+        assert_eq!(parsed.answers[0].domain(), "example.com");
+        assert_eq!(parsed.answers[0].ttl(), 300);
+    }
+
+    #[test]
+    fn round_trips_unknown_record_type_verbatim() {
+        // 模拟一条 EDNS OPT 记录（type 41）：rdata 和 class 必须原样往返，
+        // 否则 header 里的计数会和实际写出的字节对不上
+        let mut packet = DnsPacket::new();
+        packet.header.id = 0xabcd;
+        packet.answers.push(DnsRecord::Unknown {
+            domain: "".to_string(),
+            qtype: 41,
+            class: 4096,
+            ttl: 0,
+            rdata: vec![0x00, 0x0a, 0x00, 0x08, 1, 2, 3, 4, 5, 6, 7, 8],
+        });
+
+        let bytes = packet.to_bytes().unwrap();
+
+        let mut buffer = BytePacketBuffer::new(&bytes);
+        let parsed = DnsPacket::from_buffer(&mut buffer).unwrap();
+
+        assert_eq!(parsed.header.answers, 1);
+        assert_eq!(parsed.answers.len(), 1);
+        match &parsed.answers[0] {
+            DnsRecord::Unknown {
+                qtype,
+                class,
+                rdata,
+                ..
+            } => {
+                assert_eq!(*qtype, 41);
+                assert_eq!(*class, 4096);
+                assert_eq!(rdata, &vec![0x00, 0x0a, 0x00, 0x08, 1, 2, 3, 4, 5, 6, 7, 8]);
+            }
+            other => panic!("expected Unknown record, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn round_trips_txt_record_with_non_utf8_rdata() {
+        // DKIM/SPF 等 TXT 记录经常携带非法 UTF-8 字节，不能用 from_utf8_lossy 存
+        let mut packet = DnsPacket::new();
+        packet.answers.push(DnsRecord::Txt {
+            domain: "example.com".to_string(),
+            data: vec![0xff, 0xfe, 0x00, 0x41],
+            ttl: 60,
+        });
+
+        let bytes = packet.to_bytes().unwrap();
+
+        let mut buffer = BytePacketBuffer::new(&bytes);
+        let parsed = DnsPacket::from_buffer(&mut buffer).unwrap();
+
+        match &parsed.answers[0] {
+            DnsRecord::Txt { data, .. } => assert_eq!(data, &vec![0xff, 0xfe, 0x00, 0x41]),
+            other => panic!("expected Txt record, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn rejects_rdata_length_mismatch() {
+        // 声明 data_len 为 100 但缓冲区里没有那么多剩余字节
+        let mut buffer = VectorPacketBuffer::new();
+        buffer.write_qname("example.com").unwrap();
+        buffer.write_u16(QueryType::A.to_num()).unwrap();
+        buffer.write_u16(1).unwrap();
+        buffer.write_u32(60).unwrap();
+        buffer.write_u16(100).unwrap();
+
+        let bytes = buffer.into_bytes();
+        let mut read_buffer = BytePacketBuffer::new(&bytes);
+        assert!(DnsRecord::read(&mut read_buffer).is_err());
+    }
+
+    #[test]
+    fn oversized_data_len_is_rejected_as_rdata_overflow() {
+        // 声明的 data_len 超出缓冲区剩余长度，必须在读取 rdata 前就判定为
+        // RdataOverflow，而不是等某个类型的读取偶然失败
+        let mut buffer = VectorPacketBuffer::new();
+        buffer.write_qname("example.com").unwrap();
+        buffer.write_u16(41).unwrap(); // qtype 41 = EDNS OPT，走 Unknown 分支
+        buffer.write_u16(4096).unwrap();
+        buffer.write_u32(0).unwrap();
+        buffer.write_u16(1000).unwrap(); // data_len，远超剩余字节数
+
+        let bytes = buffer.into_bytes();
+        let mut read_buffer = BytePacketBuffer::new(&bytes);
+        match DnsRecord::read(&mut read_buffer) {
+            Err(ProtocolError::RdataOverflow) => {}
+            other => panic!("expected RdataOverflow, got {:?}", other),
+        }
+    }
+}
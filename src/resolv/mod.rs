@@ -0,0 +1,153 @@
+//! `/etc/resolv.conf` 解析（做法参考 mtop），让转发器可以从系统配置发现上游服务器，
+//! 而不是要求把每一台服务器都手动列在 [`crate::config::UpstreamConfig`] 里。
+
+use crate::config::{Protocol, ServerConfig, UpstreamConfig};
+use std::net::{IpAddr, SocketAddr};
+use tokio::fs;
+use tracing::warn;
+
+/// `options` 行里我们关心的几个参数
+#[derive(Debug, Clone, Default)]
+pub struct ResolvOptions {
+    pub timeout: Option<u64>,
+    pub attempts: Option<u32>,
+    pub ndots: Option<u32>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ResolvConf {
+    pub nameservers: Vec<IpAddr>,
+    pub options: ResolvOptions,
+}
+
+pub async fn parse_file(path: &str) -> Result<ResolvConf, ResolvError> {
+    let content = fs::read_to_string(path)
+        .await
+        .map_err(|e| ResolvError::Io(e.to_string()))?;
+    Ok(parse_str(&content))
+}
+
+/// 解析 `nameserver <ip>` 和 `options timeout:N attempts:N ndots:N` 行，
+/// 忽略其它指令（`search`/`domain`/`sortlist` 等目前用不到）
+pub fn parse_str(content: &str) -> ResolvConf {
+    let mut conf = ResolvConf::default();
+
+    for line in content.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+            continue;
+        }
+
+        let mut parts = line.split_whitespace();
+        match parts.next() {
+            Some("nameserver") => match parts.next().and_then(|s| s.parse::<IpAddr>().ok()) {
+                Some(addr) => conf.nameservers.push(addr),
+                None => warn!("resolv.conf 中的 nameserver 行无法解析: {}", line),
+            },
+            Some("options") => {
+                for opt in parts {
+                    if let Some((key, value)) = opt.split_once(':') {
+                        match key {
+                            "timeout" => conf.options.timeout = value.parse().ok(),
+                            "attempts" => conf.options.attempts = value.parse().ok(),
+                            "ndots" => conf.options.ndots = value.parse().ok(),
+                            _ => {}
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    conf
+}
+
+/// 把解析出的 nameserver 列表转换成 [`UpstreamConfig`]：固定走 53 端口 UDP，
+/// 超时时间取 `options timeout:N`，没有则使用调用方传入的默认值
+pub fn to_upstreams(conf: &ResolvConf, default_timeout: u64, base_priority: u32) -> Vec<UpstreamConfig> {
+    let timeout = conf.options.timeout.unwrap_or(default_timeout);
+
+    conf.nameservers
+        .iter()
+        .enumerate()
+        .map(|(i, addr)| UpstreamConfig {
+            name: format!("resolv.conf-{}", addr),
+            addr: SocketAddr::new(*addr, 53),
+            protocol: Protocol::UDP,
+            priority: base_priority + i as u32,
+            timeout,
+            tls_server_name: None,
+            doh_url: None,
+        })
+        .collect()
+}
+
+/// 合并手动配置的上游与从 resolv.conf 发现的上游，按 priority 排序；
+/// 返回合并后的列表，以及从 `options attempts:N` 解析出的重试轮数（默认 1）
+pub async fn merge_with_config(
+    configured: &[UpstreamConfig],
+    server_config: &ServerConfig,
+) -> (Vec<UpstreamConfig>, u32) {
+    let mut upstreams = configured.to_vec();
+    let mut attempts = 1;
+
+    if server_config.upstreams_from_resolv_conf {
+        match parse_file(&server_config.resolv_conf_path).await {
+            Ok(conf) => {
+                attempts = conf.options.attempts.unwrap_or(1).max(1);
+                let base_priority = upstreams.iter().map(|u| u.priority).max().unwrap_or(0) + 1;
+                upstreams.extend(to_upstreams(&conf, server_config.timeout, base_priority));
+            }
+            Err(e) => warn!("读取 {} 失败: {}", server_config.resolv_conf_path, e),
+        }
+    }
+
+    upstreams.sort_by_key(|u| u.priority);
+    (upstreams, attempts)
+}
+
+#[derive(Debug)]
+pub enum ResolvError {
+    Io(String),
+}
+
+impl std::fmt::Display for ResolvError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ResolvError::Io(msg) => write!(f, "读取 resolv.conf 失败: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for ResolvError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_nameservers_and_options() {
+        let content = "\
+# comment
+nameserver 1.1.1.1
+nameserver 2606:4700:4700::1111
+options timeout:2 attempts:3 ndots:1
+";
+        let conf = parse_str(content);
+        assert_eq!(conf.nameservers.len(), 2);
+        assert_eq!(conf.options.timeout, Some(2));
+        assert_eq!(conf.options.attempts, Some(3));
+        assert_eq!(conf.options.ndots, Some(1));
+    }
+
+    #[test]
+    fn to_upstreams_defaults_to_port_53_udp() {
+        let conf = parse_str("nameserver 9.9.9.9\n");
+        let upstreams = to_upstreams(&conf, 5, 100);
+        assert_eq!(upstreams.len(), 1);
+        assert_eq!(upstreams[0].addr.port(), 53);
+        assert!(matches!(upstreams[0].protocol, Protocol::UDP));
+        assert_eq!(upstreams[0].timeout, 5);
+    }
+}
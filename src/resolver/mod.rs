@@ -1,6 +1,14 @@
-use crate::config::{UpstreamConfig, Protocol};
+use crate::config::{Protocol, UpstreamConfig};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
 use tokio::net::UdpSocket;
+use tokio::sync::Mutex;
 use tokio::time::{timeout, Duration};
+use tokio_rustls::client::TlsStream;
+use tokio_rustls::rustls::pki_types::ServerName;
+use tokio_rustls::rustls::{ClientConfig, RootCertStore};
+use tokio_rustls::TlsConnector;
 use tracing::{info, error, warn};
 
 pub type DnsMessage = Vec<u8>;
@@ -9,33 +17,68 @@ pub type DnsMessage = Vec<u8>;
 pub struct DnsResolver {
     upstreams: Vec<UpstreamConfig>,
     current_upstream: usize,
+    /// 每个上游失败后的最大重试轮数，来自 resolv.conf 的 `options attempts:N`
+    max_attempts: u32,
+    /// 按上游名字缓存的 DoT 长连接，避免每次查询都重新握手
+    tls_connections: HashMap<String, TlsStream<tokio::net::TcpStream>>,
+    /// DoH 共用的 HTTP 客户端，内部自带连接池，天然支持长连接复用
+    http_client: reqwest::Client,
 }
 
 impl DnsResolver {
     pub fn new(upstreams: Vec<UpstreamConfig>) -> Self {
+        Self::with_attempts(upstreams, 1)
+    }
+
+    pub fn with_attempts(upstreams: Vec<UpstreamConfig>, max_attempts: u32) -> Self {
         Self {
             upstreams,
             current_upstream: 0,
+            max_attempts: max_attempts.max(1),
+            tls_connections: HashMap::new(),
+            http_client: reqwest::Client::new(),
         }
     }
 
+    /// 替换上游服务器列表（例如收到 SIGHUP 后重新读取 resolv.conf）
+    pub fn set_upstreams(&mut self, upstreams: Vec<UpstreamConfig>) {
+        self.upstreams = upstreams;
+        self.current_upstream = 0;
+        // 上游列表变了，旧的 DoT 长连接可能已经不对应任何配置，直接清空重建
+        self.tls_connections.clear();
+    }
+
+    /// 更新每个上游的最大重试轮数
+    pub fn set_max_attempts(&mut self, max_attempts: u32) {
+        self.max_attempts = max_attempts.max(1);
+    }
+
     /// 解析DNS查询
     pub async fn resolve(&mut self, query: &DnsMessage) -> Result<DnsMessage, ResolverError> {
-        // 尝试所有上游服务器
-        for _attempt in 0..self.upstreams.len() {
-            let upstream = &self.upstreams[self.current_upstream];
-            
-            info!("尝试使用上游服务器: {}", upstream.name);
-            
-            match self.query_upstream(upstream, query).await {
-                Ok(response) => {
-                    info!("从上游服务器 {} 获得响应", upstream.name);
-                    return Ok(response);
-                }
-                Err(e) => {
-                    warn!("上游服务器 {} 查询失败: {}", upstream.name, e);
-                    // 切换到下一个上游服务器
-                    self.current_upstream = (self.current_upstream + 1) % self.upstreams.len();
+        if self.upstreams.is_empty() {
+            error!("没有可用的上游服务器");
+            return Err(ResolverError::AllUpstreamsUnavailable);
+        }
+
+        // 每一轮尝试所有上游服务器，最多重复 max_attempts 轮
+        for round in 0..self.max_attempts {
+            for _attempt in 0..self.upstreams.len() {
+                // 克隆一份配置，避免 self.upstreams 的不可变借用和下面 query_upstream 需要的
+                // 可变借用（维护 DoT 长连接缓存）冲突
+                let upstream = self.upstreams[self.current_upstream].clone();
+
+                info!("尝试使用上游服务器: {} (第 {} 轮)", upstream.name, round + 1);
+
+                match self.query_upstream(&upstream, query).await {
+                    Ok(response) => {
+                        info!("从上游服务器 {} 获得响应", upstream.name);
+                        return Ok(response);
+                    }
+                    Err(e) => {
+                        warn!("上游服务器 {} 查询失败: {}", upstream.name, e);
+                        // 切换到下一个上游服务器
+                        self.current_upstream = (self.current_upstream + 1) % self.upstreams.len();
+                    }
                 }
             }
         }
@@ -46,23 +89,15 @@ impl DnsResolver {
 
     /// 查询特定的上游服务器
     async fn query_upstream(
-        &self,
+        &mut self,
         upstream: &UpstreamConfig,
         query: &DnsMessage,
     ) -> Result<DnsMessage, ResolverError> {
         match upstream.protocol {
             Protocol::UDP => self.query_udp(upstream, query).await,
             Protocol::TCP => self.query_tcp(upstream, query).await,
-            Protocol::DoT => {
-                // TODO: 实现 DNS over TLS
-                error!("DNS over TLS 暂未实现");
-                Err(ResolverError::UnsupportedProtocol)
-            }
-            Protocol::DoH => {
-                // TODO: 实现 DNS over HTTPS
-                error!("DNS over HTTPS 暂未实现");
-                Err(ResolverError::UnsupportedProtocol)
-            }
+            Protocol::DoT => self.query_tls(upstream, query).await,
+            Protocol::DoH => self.query_https(upstream, query).await,
         }
     }
 
@@ -135,18 +170,182 @@ impl DnsResolver {
         Ok(response)
     }
 
+    /// DNS over TLS 查询：优先复用已缓存的长连接，连接已失效则重新握手一次
+    async fn query_tls(
+        &mut self,
+        upstream: &UpstreamConfig,
+        query: &DnsMessage,
+    ) -> Result<DnsMessage, ResolverError> {
+        if let Some(stream) = self.tls_connections.get_mut(&upstream.name) {
+            match send_framed(stream, query, upstream.timeout).await {
+                Ok(response) => return Ok(response),
+                Err(e) => {
+                    warn!("复用到 {} 的 TLS 长连接失败，重新建立: {}", upstream.name, e);
+                    self.tls_connections.remove(&upstream.name);
+                }
+            }
+        }
+
+        let mut stream = connect_tls(upstream).await?;
+        let response = send_framed(&mut stream, query, upstream.timeout).await?;
+        self.tls_connections.insert(upstream.name.clone(), stream);
+        Ok(response)
+    }
+
+    /// DNS over HTTPS 查询：POST 线格式报文，`http_client` 自带连接池复用 TCP/TLS 连接
+    async fn query_https(
+        &self,
+        upstream: &UpstreamConfig,
+        query: &DnsMessage,
+    ) -> Result<DnsMessage, ResolverError> {
+        let url = upstream.doh_url.as_ref().ok_or_else(|| {
+            ResolverError::NetworkError(format!("上游 {} 是 DoH 协议但未配置 doh_url", upstream.name))
+        })?;
+
+        let response = timeout(
+            Duration::from_secs(upstream.timeout),
+            self.http_client
+                .post(url)
+                .header("content-type", "application/dns-message")
+                .body(query.clone())
+                .send(),
+        )
+        .await
+        .map_err(|_| ResolverError::Timeout)?
+        .map_err(|e| ResolverError::NetworkError(e.to_string()))?;
+
+        let response = response
+            .error_for_status()
+            .map_err(|e| ResolverError::NetworkError(e.to_string()))?;
+
+        response
+            .bytes()
+            .await
+            .map(|bytes| bytes.to_vec())
+            .map_err(|e| ResolverError::NetworkError(e.to_string()))
+    }
+
     /// 获取当前上游服务器信息
     pub fn current_upstream(&self) -> Option<&UpstreamConfig> {
         self.upstreams.get(self.current_upstream)
     }
 }
 
+/// 按分片加锁的 `DnsResolver` 池：`resolve` 本身要跨网络 IO，如果所有 UDP worker
+/// 共用同一把 `Mutex<DnsResolver>`，上游转发就会被串行化到并发度 1，抵消
+/// SO_REUSEPORT 多 socket 分担负载的效果。每个分片各自持有独立的 `current_upstream`
+/// 游标和 DoT 长连接缓存，用轮询分配查询，换来转发阶段的真正并发。
+pub struct ResolverPool {
+    shards: Vec<Mutex<DnsResolver>>,
+    next: AtomicUsize,
+}
+
+impl ResolverPool {
+    pub fn new(upstreams: Vec<UpstreamConfig>, max_attempts: u32, shard_count: usize) -> Self {
+        let shard_count = shard_count.max(1);
+        let shards = (0..shard_count)
+            .map(|_| Mutex::new(DnsResolver::with_attempts(upstreams.clone(), max_attempts)))
+            .collect();
+
+        Self {
+            shards,
+            next: AtomicUsize::new(0),
+        }
+    }
+
+    /// 轮询选出一个分片并转发查询
+    pub async fn resolve(&self, query: &DnsMessage) -> Result<DnsMessage, ResolverError> {
+        let idx = self.next.fetch_add(1, Ordering::Relaxed) % self.shards.len();
+        self.shards[idx].lock().await.resolve(query).await
+    }
+
+    /// 把新的上游列表广播给每一个分片（例如 SIGHUP 重新读取 resolv.conf 之后）
+    pub async fn set_upstreams(&self, upstreams: Vec<UpstreamConfig>) {
+        for shard in &self.shards {
+            shard.lock().await.set_upstreams(upstreams.clone());
+        }
+    }
+
+    /// 把新的最大重试轮数广播给每一个分片
+    pub async fn set_max_attempts(&self, max_attempts: u32) {
+        for shard in &self.shards {
+            shard.lock().await.set_max_attempts(max_attempts);
+        }
+    }
+}
+
+/// 建立到上游的 TLS 连接，使用 `webpki_roots` 内置的 CA 根证书校验服务器证书，
+/// 校验的服务器名优先取 `tls_server_name`，否则退化为上游地址的 IP
+async fn connect_tls(upstream: &UpstreamConfig) -> Result<TlsStream<tokio::net::TcpStream>, ResolverError> {
+    let tcp = timeout(
+        Duration::from_secs(upstream.timeout),
+        tokio::net::TcpStream::connect(upstream.addr),
+    )
+    .await
+    .map_err(|_| ResolverError::Timeout)?
+    .map_err(|e| ResolverError::NetworkError(e.to_string()))?;
+
+    let mut root_store = RootCertStore::empty();
+    root_store.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+    let tls_config = ClientConfig::builder()
+        .with_root_certificates(root_store)
+        .with_no_client_auth();
+    let connector = TlsConnector::from(Arc::new(tls_config));
+
+    let server_name_str = upstream
+        .tls_server_name
+        .clone()
+        .unwrap_or_else(|| upstream.addr.ip().to_string());
+    let server_name = ServerName::try_from(server_name_str.clone())
+        .map_err(|_| ResolverError::NetworkError(format!("非法的 TLS 服务器名称: {}", server_name_str)))?;
+
+    timeout(
+        Duration::from_secs(upstream.timeout),
+        connector.connect(server_name, tcp),
+    )
+    .await
+    .map_err(|_| ResolverError::Timeout)?
+    .map_err(|e| ResolverError::NetworkError(e.to_string()))
+}
+
+/// 在已建立的流上按 2 字节长度前缀发送一条查询并读回一条响应，DoT 和普通 TCP 共用的帧格式
+async fn send_framed(
+    stream: &mut TlsStream<tokio::net::TcpStream>,
+    query: &DnsMessage,
+    timeout_secs: u64,
+) -> Result<DnsMessage, ResolverError> {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let mut message = Vec::with_capacity(2 + query.len());
+    message.extend_from_slice(&(query.len() as u16).to_be_bytes());
+    message.extend_from_slice(query);
+
+    timeout(Duration::from_secs(timeout_secs), stream.write_all(&message))
+        .await
+        .map_err(|_| ResolverError::Timeout)?
+        .map_err(|e| ResolverError::NetworkError(e.to_string()))?;
+
+    let mut len_bytes = [0u8; 2];
+    timeout(Duration::from_secs(timeout_secs), stream.read_exact(&mut len_bytes))
+        .await
+        .map_err(|_| ResolverError::Timeout)?
+        .map_err(|e| ResolverError::NetworkError(e.to_string()))?;
+    let response_len = u16::from_be_bytes(len_bytes) as usize;
+
+    let mut response = vec![0u8; response_len];
+    timeout(Duration::from_secs(timeout_secs), stream.read_exact(&mut response))
+        .await
+        .map_err(|_| ResolverError::Timeout)?
+        .map_err(|e| ResolverError::NetworkError(e.to_string()))?;
+
+    Ok(response)
+}
+
 #[derive(Debug)]
 pub enum ResolverError {
     AllUpstreamsUnavailable,
     NetworkError(String),
     Timeout,
-    UnsupportedProtocol,
 }
 
 impl std::fmt::Display for ResolverError {
@@ -155,7 +354,6 @@ impl std::fmt::Display for ResolverError {
             ResolverError::AllUpstreamsUnavailable => write!(f, "所有上游服务器不可用"),
             ResolverError::NetworkError(msg) => write!(f, "网络错误: {}", msg),
             ResolverError::Timeout => write!(f, "请求超时"),
-            ResolverError::UnsupportedProtocol => write!(f, "不支持的协议"),
         }
     }
 }
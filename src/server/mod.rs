@@ -1,23 +1,295 @@
+use crate::authority::{Authority, AuthorityAnswer};
 use crate::cache::DnsCache;
 use crate::config::Config;
+use crate::message::{self, Message};
 use crate::middleware::MiddlewarePipeline;
+use crate::middleware::blocklist::BlocklistMiddleware;
 use crate::middleware::logging::LoggingMiddleware;
 use crate::middleware::metrics::MetricsMiddleware;
 use crate::middleware::rate_limit::RateLimitMiddleware;
-use crate::resolver::DnsResolver;
+use crate::protocol::{DnsPacket, ResultCode};
+use crate::resolv;
+use crate::resolver::ResolverPool;
 
+use socket2::{Domain, Protocol as SockProtocol, Socket, Type};
 use std::net::SocketAddr;
 use std::sync::Arc;
-use tokio::net::UdpSocket;
-use tokio::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream, UdpSocket};
+use tokio::sync::Semaphore;
 use tracing::{debug, error, info, warn};
 
+/// 绑定一个设置了 `SO_REUSEPORT` 的 UDP socket：多个这样的 socket 可以共享同一个
+/// 地址，由内核在它们之间负载均衡入站数据报
+fn bind_reuseport_udp_socket(addr: SocketAddr) -> std::io::Result<UdpSocket> {
+    let socket = Socket::new(Domain::for_address(addr), Type::DGRAM, Some(SockProtocol::UDP))?;
+    socket.set_reuse_address(true)?;
+    #[cfg(unix)]
+    socket.set_reuse_port(true)?;
+    socket.set_nonblocking(true)?;
+    socket.bind(&addr.into())?;
+    UdpSocket::from_std(socket.into())
+}
+
+/// 启动一个极简的 HTTP/1.1 服务端，任何请求都回以当前的 Prometheus 文本格式指标；
+/// 不做路由或方法校验，只是给 Prometheus/VictoriaMetrics 这类抓取器一个可抓的端点
+async fn spawn_metrics_endpoint(
+    listen_addr: SocketAddr,
+    metrics: Arc<MetricsMiddleware>,
+) -> std::io::Result<()> {
+    let listener = TcpListener::bind(listen_addr).await?;
+    info!("启动指标抓取端点在地址: {}", listen_addr);
+
+    loop {
+        let (mut stream, client_addr) = match listener.accept().await {
+            Ok(v) => v,
+            Err(e) => {
+                error!("指标端点接受连接错误: {}", e);
+                continue;
+            }
+        };
+        let metrics = metrics.clone();
+
+        tokio::spawn(async move {
+            // 请求内容无关紧要，读一点丢弃掉即可，重点是回应内容
+            let mut discard = [0u8; 1024];
+            let _ = stream.read(&mut discard).await;
+
+            let body = metrics.render_prometheus().await;
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            );
+
+            if let Err(e) = stream.write_all(response.as_bytes()).await {
+                debug!("向 {} 发送指标响应失败: {}", client_addr, e);
+            }
+        });
+    }
+}
+
 /// DNS转发服务器
 pub struct DnsServer {
     config: Config,
     middleware_pipeline: Arc<MiddlewarePipeline>,
-    resolver: Arc<Mutex<DnsResolver>>,
+    resolver: Arc<ResolverPool>,
+    cache: Arc<DnsCache>,
+    authority: Arc<Authority>,
+    /// 和注册进管道的是同一个实例，这样 `process_query` 也能直接调用
+    /// `record_cache_hit`/`record_upstream_latency` 等管道覆盖不到的统计点
+    metrics: Arc<MetricsMiddleware>,
+}
+
+/// 检查查询是否落在本地权威区域内，命中则直接构造应答报文（设置 AA 位）
+async fn authority_answer(authority: &Authority, query_message: &Message) -> Option<Vec<u8>> {
+    let question = query_message.questions.first()?;
+    let answer = authority.resolve(&question.name, question.qtype).await?;
+
+    let mut response = DnsPacket::new();
+    response.header.id = query_message.header.id;
+    response.header.response = true;
+    response.header.recursion_desired = query_message.header.recursion_desired;
+    response.header.recursion_available = false;
+    response.header.authoritative_answer = true;
+    response.questions = query_message.questions.clone();
+
+    match answer {
+        AuthorityAnswer::Records(records) => {
+            response.answers = records;
+        }
+        AuthorityAnswer::NoData(soa) => {
+            response.authorities.push(soa);
+        }
+        AuthorityAnswer::NxDomain(soa) => {
+            response.header.set_result_code(ResultCode::NxDomain);
+            response.authorities.push(soa);
+        }
+    }
+
+    response.to_bytes().ok()
+}
+
+/// 处理一次查询，产出要发给客户端的响应字节：依次经过中间件请求阶段、
+/// 本地权威区域、缓存，最后才转发给上游。UDP 和 TCP 两条路径共用这一逻辑。
+///
+/// 中间件只看到解析后的 `Message`，不再接触原始字节；缓存和解析器仍然以
+/// 线格式字节为单位工作，因此响应字节需要在喂给响应中间件前重新解析一次。
+#[allow(clippy::too_many_arguments)]
+async fn process_query(
+    pipeline: &MiddlewarePipeline,
+    authority: &Authority,
+    cache: &DnsCache,
+    resolver: &ResolverPool,
+    metrics: &MetricsMiddleware,
+    cache_enabled: bool,
+    query: Vec<u8>,
+    client_addr: SocketAddr,
+) -> Option<Vec<u8>> {
+    let query_message = match message::parse(&query) {
+        Ok(m) => m,
+        Err(e) => {
+            debug!("查询报文解析失败: {}", e);
+            return None;
+        }
+    };
+
+    if let Some(question) = query_message.questions.first() {
+        metrics.record_query_type(question.qtype).await;
+    }
+
+    match pipeline.handle_request(&query_message, client_addr).await {
+        Ok(Some(mut short_circuit)) => {
+            metrics.record_rcode(short_circuit.header.result_code()).await;
+            return message::to_bytes(&mut short_circuit).ok();
+        }
+        Ok(None) => {}
+        Err(e) => {
+            debug!("请求被中间件拒绝: {}", e);
+            return None;
+        }
+    }
+
+    if let Some(resp) = authority_answer(authority, &query_message).await {
+        debug!("命中本地权威区域, 直接应答");
+        return Some(run_response_middleware(pipeline, &query_message, resp, client_addr, metrics).await);
+    }
+
+    if cache_enabled {
+        match cache.get(&query).await {
+            Some(cached) => {
+                debug!("命中缓存, 直接返回");
+                metrics.record_cache_hit();
+                return Some(
+                    run_response_middleware(pipeline, &query_message, cached, client_addr, metrics).await,
+                );
+            }
+            None => metrics.record_cache_miss(),
+        }
+    }
+
+    let upstream_start = Instant::now();
+    let upstream_resp = resolver.resolve(&query).await;
+    metrics.record_upstream_latency(upstream_start.elapsed()).await;
+
+    let response = match upstream_resp {
+        Ok(r) => r,
+        Err(e) => {
+            warn!("上游解析失败: {}", e);
+            return None;
+        }
+    };
+
+    if cache_enabled {
+        cache.put(&query, response.clone(), None).await;
+    }
+
+    Some(run_response_middleware(pipeline, &query_message, response, client_addr, metrics).await)
+}
+
+/// 把响应字节解析为 `Message` 喂给响应中间件。如果没有任何中间件实际改动消息
+/// （绝大多数查询都是如此，没有中间件需要改写响应），直接返回原始字节，不走
+/// 重新序列化这条路径——即便 writer 现在是无损的，也没必要为不需要改动的响应
+/// 多绕一圈解析/编码。解析失败（理论上不该发生，响应来自缓存或我们自己构造）
+/// 同样原样放行。
+async fn run_response_middleware(
+    pipeline: &MiddlewarePipeline,
+    query_message: &Message,
+    response_bytes: Vec<u8>,
+    client_addr: SocketAddr,
+    metrics: &MetricsMiddleware,
+) -> Vec<u8> {
+    let mut response_message = match message::parse(&response_bytes) {
+        Ok(m) => m,
+        Err(e) => {
+            debug!("响应报文解析失败，跳过响应中间件: {}", e);
+            return response_bytes;
+        }
+    };
+
+    metrics.record_rcode(response_message.header.result_code()).await;
+
+    let before_middleware = response_message.clone();
+
+    if let Err(e) = pipeline
+        .handle_response(query_message, &mut response_message, client_addr)
+        .await
+    {
+        debug!("响应中间件处理失败: {}", e);
+        return response_bytes;
+    }
+
+    if response_message == before_middleware {
+        return response_bytes;
+    }
+
+    message::to_bytes(&mut response_message).unwrap_or(response_bytes)
+}
+
+/// 处理一条 TCP 连接：按 2 字节大端长度前缀读出一条条查询，支持同一连接上
+/// 的多次串行查询，空闲超过 `idle_timeout` 或对端关闭则结束
+#[allow(clippy::too_many_arguments)]
+async fn handle_tcp_connection(
+    mut stream: TcpStream,
+    client_addr: SocketAddr,
+    pipeline: Arc<MiddlewarePipeline>,
+    authority: Arc<Authority>,
     cache: Arc<DnsCache>,
+    resolver: Arc<ResolverPool>,
+    metrics: Arc<MetricsMiddleware>,
+    cache_enabled: bool,
+    idle_timeout: Duration,
+) {
+    loop {
+        let mut len_buf = [0u8; 2];
+        match tokio::time::timeout(idle_timeout, stream.read_exact(&mut len_buf)).await {
+            Ok(Ok(_)) => {}
+            Ok(Err(_)) => break, // 对端关闭连接或读取出错
+            Err(_) => {
+                debug!("TCP连接 {} 空闲超时，关闭", client_addr);
+                break;
+            }
+        }
+
+        let msg_len = u16::from_be_bytes(len_buf) as usize;
+        let mut query = vec![0u8; msg_len];
+        match tokio::time::timeout(idle_timeout, stream.read_exact(&mut query)).await {
+            Ok(Ok(_)) => {}
+            Ok(Err(e)) => {
+                debug!("读取TCP查询体失败: {}", e);
+                break;
+            }
+            Err(_) => {
+                debug!("TCP连接 {} 读取查询体超时，关闭", client_addr);
+                break;
+            }
+        }
+
+        let Some(response) = process_query(
+            &pipeline,
+            &authority,
+            &cache,
+            &resolver,
+            &metrics,
+            cache_enabled,
+            query,
+            client_addr,
+        )
+        .await
+        else {
+            continue;
+        };
+
+        let mut framed = Vec::with_capacity(2 + response.len());
+        framed.extend_from_slice(&(response.len() as u16).to_be_bytes());
+        framed.extend_from_slice(&response);
+
+        if let Err(e) = stream.write_all(&framed).await {
+            warn!("TCP发送响应给 {} 失败: {}", client_addr, e);
+            break;
+        }
+    }
 }
 
 impl DnsServer {
@@ -32,30 +304,75 @@ impl DnsServer {
             middleware_pipeline.add_middleware(Box::new(logging_middleware));
         }
 
+        // 创建指标中间件：同一个实例既注册进管道统计请求/响应，也保留在 `Self` 上
+        // 供 `process_query` 记录管道之外的统计点（缓存命中率、上游延迟）；
+        // 先创建好实例，这样限流/黑名单中间件可以拿到计数器句柄直接自增，而不必
+        // 把统计逻辑耦合进管道本身（实例本身仍然在下面按原有顺序注册进管道）
+        let metrics = Arc::new(MetricsMiddleware::new(config.middleware.metrics_enabled));
+
         // 添加限流中间件
         if config.middleware.rate_limiting.enabled {
+            let rate_limited_counter = if config.middleware.metrics_enabled {
+                Some(metrics.rate_limited_counter())
+            } else {
+                None
+            };
             let rate_limit_middleware = RateLimitMiddleware::new(
                 true,
                 config.middleware.rate_limiting.requests_per_second,
                 config.middleware.rate_limiting.burst_size,
+                config.middleware.rate_limiting.max_tracked_clients,
+                Duration::from_secs(config.middleware.rate_limiting.idle_timeout_secs),
+                config.middleware.rate_limiting.ipv4_prefix_len,
+                config.middleware.rate_limiting.ipv6_prefix_len,
+                rate_limited_counter,
             );
             middleware_pipeline.add_middleware(Box::new(rate_limit_middleware));
         }
 
-        // 创建指标中间件
-        let metrics = Arc::new(MetricsMiddleware::new(config.middleware.metrics_enabled));
-        if config.middleware.metrics_enabled {
-            // 创建一个新的指标中间件实例来添加到管道
-            let metrics_middleware = MetricsMiddleware::new(true);
-            middleware_pipeline.add_middleware(Box::new(metrics_middleware));
+        let blocked_counter = if config.middleware.metrics_enabled {
+            middleware_pipeline.add_middleware(Box::new(metrics.clone()));
+            Some(metrics.blocked_counter())
+        } else {
+            None
+        };
+
+        // 添加域名黑名单中间件
+        if config.middleware.blocklist.enabled {
+            let blocklist_middleware = BlocklistMiddleware::new(
+                true,
+                config.middleware.blocklist.rule_files.clone(),
+                config.middleware.blocklist.allowlist_files.clone(),
+                config.middleware.blocklist.action,
+                Duration::from_secs(config.middleware.blocklist.reload_interval_secs),
+                blocked_counter,
+            )
+            .await;
+            middleware_pipeline.add_middleware(Box::new(blocklist_middleware));
         }
 
-        // 创建解析器
-        let resolver = Arc::new(Mutex::new(DnsResolver::new(config.upstreams.clone())));
+        // 创建解析器：如果启用了 resolv.conf 发现，和手动配置的上游合并
+        let (upstreams, max_attempts) =
+            resolv::merge_with_config(&config.upstreams, &config.server).await;
+        info!(
+            "解析器上游数量: {} (resolv.conf 发现: {})",
+            upstreams.len(),
+            config.server.upstreams_from_resolv_conf
+        );
+        // 解析器分片数等于 UDP worker 数，查询轮询分配到各自独立加锁的分片上，
+        // 避免单把全局锁把所有上游转发串行化，抵消 SO_REUSEPORT 多 socket 分担负载的效果
+        let resolver = Arc::new(ResolverPool::new(
+            upstreams,
+            max_attempts,
+            config.server.udp_socket_count.max(1),
+        ));
 
         // 创建缓存
         let cache = Arc::new(DnsCache::new(&config.cache));
 
+        // 加载本地权威区域
+        let authority = Arc::new(Authority::load(&config.zones).await);
+
         info!("DNS服务器初始化完成");
         info!("监听地址: {}", config.server.listen_addr);
         info!("UDP启用: {}", config.server.udp_enabled);
@@ -68,6 +385,8 @@ impl DnsServer {
             middleware_pipeline: Arc::new(middleware_pipeline),
             resolver,
             cache,
+            authority,
+            metrics,
         })
     }
 
@@ -80,107 +399,174 @@ impl DnsServer {
     pub async fn run(&self) -> Result<(), DnsServerError> {
         info!("启动DNS服务器在地址: {}", self.config.server.listen_addr);
 
-        // 启动UDP服务器
+        // 收到 SIGHUP 时重新读取 resolv.conf，拾取 DHCP 下发的上游变更
+        if self.config.server.upstreams_from_resolv_conf {
+            let resolver = self.resolver.clone();
+            let server_config = self.config.server.clone();
+            let configured_upstreams = self.config.upstreams.clone();
+
+            match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup()) {
+                Ok(mut sighup) => {
+                    tokio::spawn(async move {
+                        loop {
+                            sighup.recv().await;
+                            info!("收到 SIGHUP，重新加载 resolv.conf");
+                            let (upstreams, max_attempts) =
+                                resolv::merge_with_config(&configured_upstreams, &server_config)
+                                    .await;
+                            resolver.set_upstreams(upstreams).await;
+                            resolver.set_max_attempts(max_attempts).await;
+                        }
+                    });
+                }
+                Err(e) => warn!("注册 SIGHUP 处理器失败: {}", e),
+            }
+        }
+
+        // 启动UDP服务器：绑定 `udp_socket_count` 个共享同一地址的 SO_REUSEPORT socket，
+        // 每个 socket 各跑一条独立的接收循环，由内核在它们之间负载均衡入站数据报，
+        // 避免单个 socket/单条接收循环成为高查询速率下的瓶颈
         if self.config.server.udp_enabled {
             let listen_addr = self.config.server.listen_addr;
+            let socket_count = self.config.server.udp_socket_count.max(1);
 
-            info!("启动UDP服务器在地址: {}", listen_addr);
+            info!(
+                "启动UDP服务器在地址: {} ({} 个 SO_REUSEPORT socket)",
+                listen_addr, socket_count
+            );
 
-            let socket = UdpSocket::bind(listen_addr)
-                .await
-                .map_err(|e| DnsServerError::NetworkError(e.to_string()))?;
-            let socket = Arc::new(socket);
             let resolver = self.resolver.clone();
             let cache = self.cache.clone();
             let pipeline = self.middleware_pipeline.clone();
-
-            // 主循环: 克隆引用供 move 使用
+            let authority = self.authority.clone();
+            let metrics = self.metrics.clone();
             let config_cache_enabled = self.config.cache.enabled;
 
-            loop {
-                let mut buffer = vec![0u8; 1500]; // 以太网MTU上限, 兼容 EDNS(不拆分)
-                let (len, client_addr) = match socket.recv_from(&mut buffer).await {
-                    Ok(v) => v,
-                    Err(e) => {
-                        error!("UDP接收错误: {}", e);
-                        continue;
-                    }
-                };
-                buffer.truncate(len);
-                let query = buffer;
-
-                let socket = socket.clone();
+            for worker_id in 0..socket_count {
+                let socket = bind_reuseport_udp_socket(listen_addr)
+                    .map_err(|e| DnsServerError::NetworkError(e.to_string()))?;
+                let socket = Arc::new(socket);
                 let resolver = resolver.clone();
                 let cache = cache.clone();
                 let pipeline = pipeline.clone();
-                // metrics 中间件统计通过中间件本身进行，这里不再手动计数
+                let authority = authority.clone();
+                let metrics = metrics.clone();
 
+                // UDP 和 TCP 需要并发服务，每条接收循环都放到后台任务里跑
                 tokio::spawn(async move {
-                    // 中间件请求阶段
-                    match pipeline.handle_request(&query, client_addr).await {
-                        Ok(Some(short_circuit)) => {
-                            let _ = socket.send_to(&short_circuit, client_addr).await;
-                            return;
-                        }
-                        Ok(None) => {}
-                        Err(e) => {
-                            debug!("请求被中间件拒绝: {}", e);
-                            return;
-                        }
-                    }
+                    debug!("UDP worker #{} 启动", worker_id);
+                    // 接收缓冲区在整条循环里复用，避免每个数据报都分配一个新的 1500 字节 Vec
+                    let mut buffer = vec![0u8; 1500]; // 以太网MTU上限, 兼容 EDNS(不拆分)
+                    loop {
+                        let (len, client_addr) = match socket.recv_from(&mut buffer).await {
+                            Ok(v) => v,
+                            Err(e) => {
+                                error!("UDP接收错误: {}", e);
+                                continue;
+                            }
+                        };
+                        let query = buffer[..len].to_vec();
 
-                    // 缓存查找
-                    if config_cache_enabled && let Some(cached) = cache.get(&query).await {
-                        debug!("命中缓存, 直接返回");
-                        let mut resp = cached.clone();
-                        if let Err(e) = pipeline
-                            .handle_response(&query, &mut resp, client_addr)
+                        let socket = socket.clone();
+                        let resolver = resolver.clone();
+                        let cache = cache.clone();
+                        let pipeline = pipeline.clone();
+                        let authority = authority.clone();
+                        let metrics = metrics.clone();
+
+                        tokio::spawn(async move {
+                            if let Some(response) = process_query(
+                                &pipeline,
+                                &authority,
+                                &cache,
+                                &resolver,
+                                &metrics,
+                                config_cache_enabled,
+                                query,
+                                client_addr,
+                            )
                             .await
-                        {
-                            debug!("响应中间件处理缓存失败: {}", e);
-                        }
-                        let _ = socket.send_to(&resp, client_addr).await;
-                        return;
+                            {
+                                if let Err(e) = socket.send_to(&response, client_addr).await {
+                                    error!("发送响应失败: {}", e);
+                                }
+                            }
+                        });
                     }
+                });
+            }
+        }
 
-                    // 上游解析
-                    let upstream_resp = {
-                        let mut resolver = resolver.lock().await;
-                        resolver.resolve(&query).await
-                    };
+        // 启动TCP服务器
+        if self.config.server.tcp_enabled {
+            let listen_addr = self.config.server.listen_addr;
+
+            info!("启动TCP服务器在地址: {}", listen_addr);
+
+            let listener = TcpListener::bind(listen_addr)
+                .await
+                .map_err(|e| DnsServerError::NetworkError(e.to_string()))?;
 
-                    let mut response = match upstream_resp {
-                        Ok(r) => r,
+            let resolver = self.resolver.clone();
+            let cache = self.cache.clone();
+            let pipeline = self.middleware_pipeline.clone();
+            let authority = self.authority.clone();
+            let metrics = self.metrics.clone();
+            let cache_enabled = self.config.cache.enabled;
+            let idle_timeout = Duration::from_secs(self.config.server.tcp_idle_timeout);
+            let connection_limiter = Arc::new(Semaphore::new(self.config.server.max_tcp_connections));
+
+            tokio::spawn(async move {
+                loop {
+                    let (stream, client_addr) = match listener.accept().await {
+                        Ok(v) => v,
                         Err(e) => {
-                            warn!("上游解析失败: {}", e);
-                            return;
+                            error!("TCP接受连接错误: {}", e);
+                            continue;
                         }
                     };
 
-                    // 写入缓存
-                    if config_cache_enabled {
-                        cache.put(&query, response.clone(), None).await;
-                    }
+                    let permit = match connection_limiter.clone().try_acquire_owned() {
+                        Ok(permit) => permit,
+                        Err(_) => {
+                            warn!("TCP并发连接数已达上限，拒绝来自 {} 的连接", client_addr);
+                            continue;
+                        }
+                    };
 
-                    // 响应中间件
-                    if let Err(e) = pipeline
-                        .handle_response(&query, &mut response, client_addr)
-                        .await
-                    {
-                        debug!("响应中间件处理失败: {}", e);
-                    }
+                    let resolver = resolver.clone();
+                    let cache = cache.clone();
+                    let pipeline = pipeline.clone();
+                    let authority = authority.clone();
+                    let metrics = metrics.clone();
 
-                    if let Err(e) = socket.send_to(&response, client_addr).await {
-                        error!("发送响应失败: {}", e);
-                    }
-                });
-            }
+                    tokio::spawn(async move {
+                        let _permit = permit;
+                        handle_tcp_connection(
+                            stream,
+                            client_addr,
+                            pipeline,
+                            authority,
+                            cache,
+                            resolver,
+                            metrics,
+                            cache_enabled,
+                            idle_timeout,
+                        )
+                        .await;
+                    });
+                }
+            });
         }
 
-        // 启动TCP服务器（如果需要）
-        if self.config.server.tcp_enabled {
-            // TODO: 实现TCP服务器
-            warn!("TCP服务器功能待实现");
+        // 启动 Prometheus 指标抓取端点
+        if let Some(metrics_addr) = self.config.middleware.metrics_listen_addr {
+            let metrics = self.metrics.clone();
+            tokio::spawn(async move {
+                if let Err(e) = spawn_metrics_endpoint(metrics_addr, metrics).await {
+                    error!("启动指标抓取端点失败: {}", e);
+                }
+            });
         }
 
         // 保持主线程运行
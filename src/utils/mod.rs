@@ -82,31 +82,6 @@ pub fn extract_query_id(data: &[u8]) -> Option<u16> {
     }
 }
 
-/// 生成DNS响应的错误消息
-pub fn create_dns_error_response(query_id: u16, error_code: u8) -> Vec<u8> {
-    let mut response = vec![0u8; 12];
-    
-    // 设置查询ID
-    response[0..2].copy_from_slice(&query_id.to_be_bytes());
-    
-    // 设置标志位：QR=1(响应), RA=1(递归可用), RCODE=error_code
-    let flags = 0x8180 | (error_code as u16);
-    response[2..4].copy_from_slice(&flags.to_be_bytes());
-    
-    // 其他字段保持为0
-    response
-}
-
-/// DNS响应代码常量
-pub mod dns_rcode {
-    pub const NO_ERROR: u8 = 0;     // 无错误
-    pub const FORMAT_ERROR: u8 = 1; // 格式错误
-    pub const SERVER_FAILURE: u8 = 2; // 服务器失败
-    pub const NAME_ERROR: u8 = 3;   // 域名不存在
-    pub const NOT_IMPLEMENTED: u8 = 4; // 未实现
-    pub const REFUSED: u8 = 5;      // 拒绝
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;